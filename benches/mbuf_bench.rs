@@ -54,16 +54,16 @@ fn mbuf_read_write_benchmark(c: &mut Criterion) {
     
     c.bench_function("mbuf_write", |b| {
         b.iter(|| {
-            buf.write(black_box(data)).unwrap();
+            buf.write_all(black_box(data)).unwrap();
             buf.clear();
         })
     });
     
     c.bench_function("mbuf_read", |b| {
         b.iter(|| {
-            buf.write(data).unwrap();
+            buf.write_all(data).unwrap();
             let mut read_buf = [0u8; 9];
-            buf.read(&mut read_buf).unwrap();
+            buf.read_exact(&mut read_buf).unwrap();
             buf.clear();
         })
     });
@@ -0,0 +1,240 @@
+// MIT License
+//
+// Copyright (c) 2023 gaosg
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! 原地AEAD加密：随机数写入headroom，认证标签写入tailroom
+//!
+//! 封装后的格式为`nonce || ciphertext || tag`，数据本身在原位置加解密，
+//! 空间足够时不会重新分配内存
+
+use std::fmt;
+use aes_gcm::aead::{AeadInPlace, KeyInit};
+use aes_gcm::{Aes128Gcm, Aes256Gcm};
+use chacha20poly1305::ChaCha20Poly1305;
+use super::MBuf;
+
+/// 随机数长度
+pub const NONCE_LEN: usize = 12;
+/// 认证标签长度
+pub const TAG_LEN: usize = 16;
+
+/// AEAD算法
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cipher {
+    Aes128Gcm,
+    Aes256Gcm,
+    ChaCha20Poly1305,
+}
+
+impl Cipher {
+    /// 密钥长度
+    pub fn key_len(self) -> usize {
+        match self {
+            Cipher::Aes128Gcm => 16,
+            Cipher::Aes256Gcm | Cipher::ChaCha20Poly1305 => 32,
+        }
+    }
+}
+
+/// 加解密错误
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// 密钥长度与算法不符
+    InvalidKey,
+    /// headroom放不下随机数或tailroom放不下认证标签
+    InsufficientRoom,
+    /// 数据或附加数据超过算法允许的长度
+    TooLong,
+    /// 数据短于随机数与认证标签之和
+    Truncated,
+    /// 认证失败，数据或附加数据被篡改
+    Authentication,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidKey => write!(f, "invalid key length"),
+            Error::InsufficientRoom => write!(f, "not enough headroom or tailroom"),
+            Error::TooLong => write!(f, "data exceeds the cipher's length limit"),
+            Error::Truncated => write!(f, "sealed data is truncated"),
+            Error::Authentication => write!(f, "authentication failed"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// 用key加密data并返回认证标签
+fn encrypt(cipher: Cipher, key: &[u8], nonce: &[u8; NONCE_LEN], aad: &[u8], data: &mut [u8]) -> Result<[u8; TAG_LEN], Error> {
+    let nonce = nonce.into();
+    let tag = match cipher {
+        Cipher::Aes128Gcm => Aes128Gcm::new_from_slice(key)
+            .map_err(|_| Error::InvalidKey)?
+            .encrypt_in_place_detached(nonce, aad, data),
+        Cipher::Aes256Gcm => Aes256Gcm::new_from_slice(key)
+            .map_err(|_| Error::InvalidKey)?
+            .encrypt_in_place_detached(nonce, aad, data),
+        Cipher::ChaCha20Poly1305 => ChaCha20Poly1305::new_from_slice(key)
+            .map_err(|_| Error::InvalidKey)?
+            .encrypt_in_place_detached(nonce, aad, data),
+    };
+    // 只有数据超过算法上限（数十GB）时才会失败
+    // 空间已由调用方检查，这里失败只可能是超出算法的长度上限
+    Ok(tag.map_err(|_| Error::TooLong)?.into())
+}
+
+/// 校验认证标签并解密data
+fn decrypt(cipher: Cipher, key: &[u8], nonce: &[u8], aad: &[u8], data: &mut [u8], tag: &[u8]) -> Result<(), Error> {
+    let (nonce, tag) = (nonce.into(), tag.into());
+    let result = match cipher {
+        Cipher::Aes128Gcm => Aes128Gcm::new_from_slice(key)
+            .map_err(|_| Error::InvalidKey)?
+            .decrypt_in_place_detached(nonce, aad, data, tag),
+        Cipher::Aes256Gcm => Aes256Gcm::new_from_slice(key)
+            .map_err(|_| Error::InvalidKey)?
+            .decrypt_in_place_detached(nonce, aad, data, tag),
+        Cipher::ChaCha20Poly1305 => ChaCha20Poly1305::new_from_slice(key)
+            .map_err(|_| Error::InvalidKey)?
+            .decrypt_in_place_detached(nonce, aad, data, tag),
+    };
+    result.map_err(|_| Error::Authentication)
+}
+
+impl MBuf {
+    /// 原地加密数据，随机数写入数据之前的headroom，认证标签写入数据之后的tailroom
+    ///
+    /// 需要至少`NONCE_LEN`字节的headroom和`TAG_LEN`字节的tailroom，
+    /// 与其他引用共享内存时先复制出独占的一份
+    pub fn seal_in_place(&mut self, cipher: Cipher, key: &[u8], nonce: &[u8; NONCE_LEN], aad: &[u8]) -> Result<(), Error> {
+        if key.len() != cipher.key_len() {
+            return Err(Error::InvalidKey);
+        }
+        if self.headroom() < NONCE_LEN || self.tailroom() < TAG_LEN {
+            return Err(Error::InsufficientRoom);
+        }
+        self.make_unique();
+        let tag = encrypt(cipher, key, nonce, aad, self)?;
+        self.append(&tag);
+        self.prepend(nonce);
+        Ok(())
+    }
+
+    /// 校验并原地解密`seal_in_place`的结果，去掉随机数和认证标签；认证失败时数据保持不变
+    pub fn open_in_place(&mut self, cipher: Cipher, key: &[u8], aad: &[u8]) -> Result<(), Error> {
+        if key.len() != cipher.key_len() {
+            return Err(Error::InvalidKey);
+        }
+        if self.len() < NONCE_LEN + TAG_LEN {
+            return Err(Error::Truncated);
+        }
+        self.make_unique();
+        let body_end = self.len() - TAG_LEN;
+        let (nonce, rest) = self.split_at_mut(NONCE_LEN);
+        let (body, tag) = rest.split_at_mut(body_end - NONCE_LEN);
+        decrypt(cipher, key, nonce, aad, body, tag)?;
+        self.truncate(body_end);
+        self.advance(NONCE_LEN);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(s: &str) -> Vec<u8> {
+        let s: String = s.split_whitespace().collect();
+        (0..s.len()).step_by(2).map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap()).collect()
+    }
+
+    fn sealable(data: &[u8]) -> MBuf {
+        let mut buf = MBuf::with_capacity(NONCE_LEN + data.len() + TAG_LEN);
+        buf.reserve_headroom(NONCE_LEN);
+        buf.append(data);
+        buf
+    }
+
+    #[test]
+    fn test_chacha20poly1305_rfc8439() {
+        // RFC 8439 §2.8.2
+        let key = hex("808182838485868788898a8b8c8d8e8f909192939495969798999a9b9c9d9e9f");
+        let nonce: [u8; NONCE_LEN] = hex("070000004041424344454647").try_into().unwrap();
+        let aad = hex("50515253c0c1c2c3c4c5c6c7");
+        let plaintext = b"Ladies and Gentlemen of the class of '99: If I could offer you only one tip for the future, sunscreen would be it.";
+
+        let mut buf = sealable(plaintext);
+        let base = buf.as_ptr();
+        buf.seal_in_place(Cipher::ChaCha20Poly1305, &key, &nonce, &aad).unwrap();
+        assert_eq!(buf.as_ptr(), base.wrapping_sub(NONCE_LEN));
+        assert_eq!(buf.len(), NONCE_LEN + plaintext.len() + TAG_LEN);
+        assert_eq!(&buf[..NONCE_LEN], &nonce);
+        assert_eq!(&buf[NONCE_LEN..NONCE_LEN + 16], &hex("d31a8d34648e60db7b86afbc53ef7ec2")[..]);
+        assert_eq!(&buf[buf.len() - TAG_LEN..], &hex("1ae10b594f09e26a7e902ecbd0600691")[..]);
+
+        buf.open_in_place(Cipher::ChaCha20Poly1305, &key, &aad).unwrap();
+        assert_eq!(buf, plaintext);
+        assert_eq!((buf.headroom(), buf.tailroom()), (NONCE_LEN, TAG_LEN));
+    }
+
+    #[test]
+    fn test_aes_gcm_vectors() {
+        // GCM规范测试用例1和2：全零密钥和随机数
+        let key = [0u8; 16];
+        let nonce = [0u8; NONCE_LEN];
+
+        let mut empty = sealable(b"");
+        empty.seal_in_place(Cipher::Aes128Gcm, &key, &nonce, b"").unwrap();
+        assert_eq!(&empty[NONCE_LEN..], &hex("58e2fccefa7e3061367f1d57a4e7455a")[..]);
+
+        let mut zeros = sealable(&[0u8; 16]);
+        zeros.seal_in_place(Cipher::Aes128Gcm, &key, &nonce, b"").unwrap();
+        assert_eq!(&zeros[NONCE_LEN..], &hex("0388dace60b6a392f328c2b971b2fe78 ab6e47d42cec13bdf53a67b21257bddf")[..]);
+        zeros.open_in_place(Cipher::Aes128Gcm, &key, b"").unwrap();
+        assert_eq!(zeros, [0u8; 16]);
+    }
+
+    #[test]
+    fn test_errors() {
+        let key = [7u8; 32];
+        let nonce = [1u8; NONCE_LEN];
+
+        let mut no_room = MBuf::with_capacity(64);
+        no_room.append(b"data");
+        assert_eq!(no_room.seal_in_place(Cipher::Aes256Gcm, &key, &nonce, b""), Err(Error::InsufficientRoom));
+        assert_eq!(no_room.seal_in_place(Cipher::Aes128Gcm, &key, &nonce, b""), Err(Error::InvalidKey));
+        assert_eq!(no_room.open_in_place(Cipher::Aes256Gcm, &key, b""), Err(Error::Truncated));
+
+        let mut buf = sealable(b"secret");
+        let shared = buf.clone();
+        buf.seal_in_place(Cipher::Aes256Gcm, &key, &nonce, b"hdr").unwrap();
+        assert_eq!(shared, b"secret");
+
+        let sealed = buf.clone();
+        assert_eq!(buf.open_in_place(Cipher::Aes256Gcm, &key, b"other"), Err(Error::Authentication));
+        assert_eq!(buf, sealed);
+        buf[NONCE_LEN] ^= 1;
+        assert_eq!(buf.open_in_place(Cipher::Aes256Gcm, &key, b"hdr"), Err(Error::Authentication));
+        buf[NONCE_LEN] ^= 1;
+        buf.open_in_place(Cipher::Aes256Gcm, &key, b"hdr").unwrap();
+        assert_eq!(buf, b"secret");
+    }
+}
@@ -0,0 +1,91 @@
+// MIT License
+//
+// Copyright (c) 2023 gaosg
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! tokio与futures-io共用的异步读写逻辑
+//!
+//! 缓冲区都在内存中，读写总是立即完成；两边只在`AsyncRead`/`AsyncWrite`的签名上不同
+
+use std::future::poll_fn;
+use std::io::{self, IoSlice};
+use std::task::{Context, Poll};
+use super::{MBuf, MChain};
+
+/// 用同步Write为类型实现立即完成的AsyncWrite，close为关闭方法名，最后的块中是附加的方法
+macro_rules! impl_async_write {
+    ($trait:path, $close:ident, [$($ty:ty),*], $extra:tt) => {
+        $(
+            impl_async_write!(@impl $trait, $close, $ty, $extra);
+        )*
+    };
+    (@impl $trait:path, $close:ident, $ty:ty, { $($extra:tt)* }) => {
+        impl $trait for $ty {
+            fn poll_write(self: Pin<&mut Self>, _cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+                Poll::Ready(self.get_mut().write(buf))
+            }
+
+            fn poll_write_vectored(
+                self: Pin<&mut Self>,
+                _cx: &mut Context<'_>,
+                bufs: &[IoSlice<'_>],
+            ) -> Poll<io::Result<usize>> {
+                Poll::Ready(self.get_mut().write_vectored(bufs))
+            }
+
+            fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+                Poll::Ready(Ok(()))
+            }
+
+            fn $close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+                Poll::Ready(Ok(()))
+            }
+
+            $($extra)*
+        }
+    };
+}
+
+pub(crate) use impl_async_write;
+
+/// read_into的公共部分：没有空闲空间时先扩容，再用poll_read读入空闲空间
+pub(crate) async fn read_into_with<F>(buf: &mut MBuf, mut poll_read: F) -> io::Result<usize>
+where
+    F: FnMut(&mut Context<'_>, &mut [u8]) -> Poll<io::Result<usize>>,
+{
+    if buf.tailroom() == 0 {
+        buf.reserve(buf.capacity().max(64));
+    }
+    let n = poll_fn(|cx| poll_read(cx, buf.spare_capacity_mut())).await?;
+    buf.commit(n);
+    Ok(n)
+}
+
+/// write_chain的公共部分：用poll_write_vectored写出链中全部数据
+pub(crate) async fn write_chain_with<F>(chain: &mut MChain, mut poll_write_vectored: F) -> io::Result<usize>
+where
+    F: FnMut(&mut Context<'_>, &[IoSlice<'_>]) -> Poll<io::Result<usize>>,
+{
+    let mut total = 0;
+    while !chain.is_empty() {
+        total += poll_fn(|cx| chain.poll_write_batch(|iov| poll_write_vectored(cx, iov))).await?;
+    }
+    Ok(total)
+}
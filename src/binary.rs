@@ -0,0 +1,790 @@
+// MIT License
+//
+// Copyright (c) 2023 gaosg
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! 紧凑的二进制serde格式，直接写入`MBuf`并从`Cursor`读取
+//!
+//! 整数使用LEB128 varint（有符号数先做zigzag），浮点数为小端序，
+//! 字符串、字节串、序列和映射以varint长度开头，枚举以varint变体序号开头。
+//! 格式不是自描述的，不支持`deserialize_any`
+
+use std::fmt;
+use serde::de::{self, DeserializeSeed, IntoDeserializer, Visitor};
+use serde::ser::{self, Serialize};
+use serde::{Deserialize, Deserializer as _};
+use super::{Cursor, MBuf};
+
+/// 序列化和反序列化错误
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// 数据在值结束前耗尽
+    Eof,
+    /// 数据不是合法的编码
+    Malformed(&'static str),
+    /// 序列或映射的长度未知
+    LengthRequired,
+    /// 格式不是自描述的，无法按任意类型解码
+    AnyNotSupported,
+    /// 值解码完成后还有剩余数据
+    TrailingBytes,
+    /// 由Serialize/Deserialize实现报告的错误
+    Message(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Eof => write!(f, "unexpected end of input"),
+            Error::Malformed(what) => write!(f, "malformed {}", what),
+            Error::LengthRequired => write!(f, "sequence length must be known in advance"),
+            Error::AnyNotSupported => write!(f, "format is not self-describing"),
+            Error::TrailingBytes => write!(f, "trailing bytes after value"),
+            Error::Message(msg) => f.write_str(msg),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl ser::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error::Message(msg.to_string())
+    }
+}
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error::Message(msg.to_string())
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// 将value编码后追加到buf
+pub fn to_mbuf<T: Serialize + ?Sized>(value: &T, buf: &mut MBuf) -> Result<()> {
+    value.serialize(&mut Serializer::new(buf))
+}
+
+/// 从游标当前位置解码一个值，游标移到该值之后
+pub fn from_cursor<'de, T: Deserialize<'de>>(cursor: &mut Cursor<'de>) -> Result<T> {
+    T::deserialize(&mut Deserializer::new(cursor))
+}
+
+/// 解码buf中的一个值，要求数据恰好用完
+pub fn from_mbuf<'de, T: Deserialize<'de>>(buf: &'de MBuf) -> Result<T> {
+    let mut cursor = Cursor::new(buf);
+    let value = from_cursor(&mut cursor)?;
+    if cursor.remaining() != 0 {
+        return Err(Error::TrailingBytes);
+    }
+    Ok(value)
+}
+
+fn zigzag(v: i128) -> u128 {
+    ((v << 1) ^ (v >> 127)) as u128
+}
+
+fn unzigzag(v: u128) -> i128 {
+    (v >> 1) as i128 ^ -((v & 1) as i128)
+}
+
+/// 向MBuf追加数据的序列化器
+pub struct Serializer<'a> {
+    buf: &'a mut MBuf,
+}
+
+impl<'a> Serializer<'a> {
+    /// 创建序列化器，数据追加到buf已有内容之后
+    pub fn new(buf: &'a mut MBuf) -> Self {
+        Self { buf }
+    }
+
+    fn write_varint(&mut self, mut v: u128) {
+        let mut out = [0u8; 19];
+        let mut n = 0;
+        loop {
+            out[n] = (v & 0x7f) as u8;
+            v >>= 7;
+            n += 1;
+            if v == 0 {
+                break;
+            }
+            out[n - 1] |= 0x80;
+        }
+        self.buf.append(&out[..n]);
+    }
+
+    fn write_len(&mut self, len: Option<usize>) -> Result<()> {
+        let len = len.ok_or(Error::LengthRequired)?;
+        self.write_varint(len as u128);
+        Ok(())
+    }
+}
+
+impl ser::Serializer for &mut Serializer<'_> {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = Self;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Self;
+    type SerializeTupleVariant = Self;
+    type SerializeMap = Self;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Self;
+
+    fn serialize_bool(self, v: bool) -> Result<()> {
+        self.buf.append(&[v as u8]);
+        Ok(())
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<()> {
+        self.buf.append(&v.to_le_bytes());
+        Ok(())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<()> {
+        self.serialize_i128(v as i128)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<()> {
+        self.serialize_i128(v as i128)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<()> {
+        self.serialize_i128(v as i128)
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<()> {
+        self.write_varint(zigzag(v));
+        Ok(())
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<()> {
+        self.buf.append(&[v]);
+        Ok(())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<()> {
+        self.serialize_u128(v as u128)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<()> {
+        self.serialize_u128(v as u128)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<()> {
+        self.serialize_u128(v as u128)
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<()> {
+        self.write_varint(v);
+        Ok(())
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<()> {
+        self.buf.append(&v.to_le_bytes());
+        Ok(())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<()> {
+        self.buf.append(&v.to_le_bytes());
+        Ok(())
+    }
+
+    fn serialize_char(self, v: char) -> Result<()> {
+        self.serialize_u32(v as u32)
+    }
+
+    fn serialize_str(self, v: &str) -> Result<()> {
+        self.serialize_bytes(v.as_bytes())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<()> {
+        self.write_varint(v.len() as u128);
+        self.buf.append(v);
+        Ok(())
+    }
+
+    fn serialize_none(self) -> Result<()> {
+        self.serialize_u8(0)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<()> {
+        self.serialize_u8(1)?;
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<()> {
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
+        Ok(())
+    }
+
+    fn serialize_unit_variant(self, _name: &'static str, index: u32, _variant: &'static str) -> Result<()> {
+        self.serialize_u32(index)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _name: &'static str, value: &T) -> Result<()> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        index: u32,
+        _variant: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.serialize_u32(index)?;
+        value.serialize(self)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self> {
+        self.write_len(len)?;
+        Ok(self)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self> {
+        Ok(self)
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Self> {
+        Ok(self)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self> {
+        self.serialize_u32(index)?;
+        Ok(self)
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self> {
+        self.write_len(len)?;
+        Ok(self)
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self> {
+        Ok(self)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self> {
+        self.serialize_u32(index)?;
+        Ok(self)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+macro_rules! impl_serialize_compound {
+    ($($trait:ident :: $method:ident),*) => {
+        $(
+            impl ser::$trait for &mut Serializer<'_> {
+                type Ok = ();
+                type Error = Error;
+
+                fn $method<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+                    value.serialize(&mut **self)
+                }
+
+                fn end(self) -> Result<()> {
+                    Ok(())
+                }
+            }
+        )*
+    };
+}
+
+impl_serialize_compound!(
+    SerializeSeq::serialize_element,
+    SerializeTuple::serialize_element,
+    SerializeTupleStruct::serialize_field,
+    SerializeTupleVariant::serialize_field
+);
+
+impl ser::SerializeMap for &mut Serializer<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<()> {
+        key.serialize(&mut **self)
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeStruct for &mut Serializer<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, _key: &'static str, value: &T) -> Result<()> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeStructVariant for &mut Serializer<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, _key: &'static str, value: &T) -> Result<()> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// 从Cursor读取数据的反序列化器，字符串和字节串直接借用底层MBuf
+pub struct Deserializer<'c, 'de> {
+    cursor: &'c mut Cursor<'de>,
+}
+
+impl<'c, 'de> Deserializer<'c, 'de> {
+    /// 创建反序列化器，从cursor的当前位置开始读取
+    pub fn new(cursor: &'c mut Cursor<'de>) -> Self {
+        Self { cursor }
+    }
+
+    fn read_byte(&mut self) -> Result<u8> {
+        self.cursor.next().ok_or(Error::Eof)
+    }
+
+    fn read_slice(&mut self, len: usize) -> Result<&'de [u8]> {
+        self.cursor.next_slice(len).ok_or(Error::Eof)
+    }
+
+    fn read_varint(&mut self) -> Result<u128> {
+        let mut value: u128 = 0;
+        for shift in (0..128).step_by(7) {
+            let byte = self.read_byte()?;
+            let bits = (byte & 0x7f) as u128;
+            if shift == 126 && bits > 0x03 {
+                break;
+            }
+            value |= bits << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(Error::Malformed("varint"))
+    }
+
+    fn read_uint<T: TryFrom<u128>>(&mut self) -> Result<T> {
+        T::try_from(self.read_varint()?).map_err(|_| Error::Malformed("integer"))
+    }
+
+    fn read_int<T: TryFrom<i128>>(&mut self) -> Result<T> {
+        T::try_from(unzigzag(self.read_varint()?)).map_err(|_| Error::Malformed("integer"))
+    }
+
+    fn read_len(&mut self) -> Result<usize> {
+        self.read_uint()
+    }
+
+    fn read_bytes(&mut self) -> Result<&'de [u8]> {
+        let len = self.read_len()?;
+        self.read_slice(len)
+    }
+
+    fn read_str(&mut self) -> Result<&'de str> {
+        std::str::from_utf8(self.read_bytes()?).map_err(|_| Error::Malformed("utf-8 string"))
+    }
+}
+
+impl<'de> de::Deserializer<'de> for &mut Deserializer<'_, 'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value> {
+        Err(Error::AnyNotSupported)
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.read_byte()? {
+            0 => visitor.visit_bool(false),
+            1 => visitor.visit_bool(true),
+            _ => Err(Error::Malformed("bool")),
+        }
+    }
+
+    fn deserialize_i8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_i8(self.read_byte()? as i8)
+    }
+
+    fn deserialize_i16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_i16(self.read_int()?)
+    }
+
+    fn deserialize_i32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_i32(self.read_int()?)
+    }
+
+    fn deserialize_i64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_i64(self.read_int()?)
+    }
+
+    fn deserialize_i128<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_i128(self.read_int()?)
+    }
+
+    fn deserialize_u8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_u8(self.read_byte()?)
+    }
+
+    fn deserialize_u16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_u16(self.read_uint()?)
+    }
+
+    fn deserialize_u32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_u32(self.read_uint()?)
+    }
+
+    fn deserialize_u64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_u64(self.read_uint()?)
+    }
+
+    fn deserialize_u128<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_u128(self.read_uint()?)
+    }
+
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let bytes = self.cursor.read_array().ok_or(Error::Eof)?;
+        visitor.visit_f32(f32::from_le_bytes(bytes))
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let bytes = self.cursor.read_array().ok_or(Error::Eof)?;
+        visitor.visit_f64(f64::from_le_bytes(bytes))
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let c = char::from_u32(self.read_uint()?).ok_or(Error::Malformed("char"))?;
+        visitor.visit_char(c)
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_borrowed_str(self.read_str()?)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_borrowed_bytes(self.read_bytes()?)
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.read_byte()? {
+            0 => visitor.visit_none(),
+            1 => visitor.visit_some(self),
+            _ => Err(Error::Malformed("option tag")),
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let len = self.read_len()?;
+        visitor.visit_seq(Access { de: self, remaining: len })
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value> {
+        visitor.visit_seq(Access { de: self, remaining: len })
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let len = self.read_len()?;
+        visitor.visit_map(Access { de: self, remaining: len })
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.deserialize_tuple(fields.len(), visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_enum(self)
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_u32(visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value> {
+        Err(Error::AnyNotSupported)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+/// 已知元素个数的序列、元组和映射访问
+struct Access<'a, 'c, 'de> {
+    de: &'a mut Deserializer<'c, 'de>,
+    remaining: usize,
+}
+
+impl<'de> de::SeqAccess<'de> for Access<'_, '_, 'de> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        seed.deserialize(&mut *self.de).map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining)
+    }
+}
+
+impl<'de> de::MapAccess<'de> for Access<'_, '_, 'de> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        seed.deserialize(&mut *self.de).map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        seed.deserialize(&mut *self.de)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining)
+    }
+}
+
+impl<'de> de::EnumAccess<'de> for &mut Deserializer<'_, 'de> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self)> {
+        let index: u32 = self.read_uint()?;
+        let value = seed.deserialize(index.into_deserializer())?;
+        Ok((value, self))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for &mut Deserializer<'_, 'de> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value> {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(self, fields: &'static [&'static str], visitor: V) -> Result<V::Value> {
+        self.deserialize_tuple(fields.len(), visitor)
+    }
+}
+
+/// 以字节串形式序列化
+impl Serialize for MBuf {
+    fn serialize<S: ser::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for MBuf {
+    fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        struct MBufVisitor;
+
+        impl<'de> Visitor<'de> for MBufVisitor {
+            type Value = MBuf;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a byte buffer")
+            }
+
+            fn visit_bytes<E: de::Error>(self, v: &[u8]) -> std::result::Result<MBuf, E> {
+                let mut buf = MBuf::with_capacity(v.len());
+                buf.append(v);
+                Ok(buf)
+            }
+
+            fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> std::result::Result<MBuf, A::Error> {
+                let mut buf = MBuf::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+                while let Some(byte) = seq.next_element::<u8>()? {
+                    buf.append(&[byte]);
+                }
+                Ok(buf)
+            }
+        }
+
+        deserializer.deserialize_byte_buf(MBufVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Kind {
+        Ping,
+        Data(u16),
+        Pair(i8, bool),
+        Named { id: u64 },
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Message<'a> {
+        seq: u32,
+        delta: i64,
+        ratio: f64,
+        name: &'a str,
+        #[serde(with = "serde_bytes")]
+        payload: &'a [u8],
+        tags: Vec<String>,
+        attrs: BTreeMap<char, Option<u8>>,
+        kinds: Vec<Kind>,
+        unit: (),
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let msg = Message {
+            seq: 300,
+            delta: -2,
+            ratio: 0.5,
+            name: "ibuf",
+            payload: b"\x00\x01\x02",
+            tags: vec!["a".into(), "bc".into()],
+            attrs: BTreeMap::from([('x', Some(7)), ('y', None)]),
+            kinds: vec![Kind::Ping, Kind::Data(9), Kind::Pair(-1, true), Kind::Named { id: 1 << 40 }],
+            unit: (),
+        };
+        let mut buf = MBuf::with_capacity(8);
+        to_mbuf(&msg, &mut buf).unwrap();
+        assert_eq!(&buf[..4], &[0xac, 0x02, 0x03, 0]);
+
+        let decoded: Message = from_mbuf(&buf).unwrap();
+        assert_eq!(decoded, msg);
+        // 字符串和字节串直接借用buf
+        assert!(buf.as_ptr_range().contains(&decoded.name.as_ptr()));
+
+        assert_eq!(from_mbuf::<Message>(&buf.slice(..buf.len() - 1)), Err(Error::Eof));
+        buf.append(&[0]);
+        assert_eq!(from_mbuf::<Message>(&buf), Err(Error::TrailingBytes));
+    }
+
+    #[test]
+    fn test_cursor_sequence() {
+        let mut buf = MBuf::with_capacity(0);
+        to_mbuf(&(i128::MIN, u128::MAX), &mut buf).unwrap();
+        to_mbuf("next", &mut buf).unwrap();
+
+        let mut cursor = Cursor::new(&buf);
+        assert_eq!(from_cursor::<(i128, u128)>(&mut cursor), Ok((i128::MIN, u128::MAX)));
+        assert_eq!(from_cursor::<String>(&mut cursor), Ok("next".to_string()));
+        assert_eq!(cursor.remaining(), 0);
+
+        let mut bad = MBuf::with_capacity(0);
+        bad.append(&[2]);
+        assert!(matches!(from_mbuf::<bool>(&bad), Err(Error::Malformed(_))));
+        assert_eq!(from_mbuf::<serde::de::IgnoredAny>(&bad), Err(Error::AnyNotSupported));
+    }
+
+    #[test]
+    fn test_mbuf_as_bytes() {
+        let mut payload = MBuf::with_capacity(4);
+        payload.append(b"pooled");
+        let mut buf = MBuf::with_capacity(0);
+        to_mbuf(&payload, &mut buf).unwrap();
+        assert_eq!(&buf[..], b"\x06pooled");
+
+        let decoded: MBuf = from_mbuf(&buf).unwrap();
+        assert_eq!(&decoded[..], b"pooled");
+    }
+}
@@ -0,0 +1,405 @@
+// MIT License
+//
+// Copyright (c) 2023 gaosg
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! RFC 1071互联网校验和与CRC32C
+
+use std::ops::RangeBounds;
+use super::{MBuf, MChain};
+use crate::mbuf::bounds;
+
+/// RFC 1071互联网校验和的增量计算器
+///
+/// 可以分多次累加任意长度的数据，正确处理奇数长度分段的边界
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Checksum {
+    sum: u64,
+    odd: bool,
+}
+
+impl Checksum {
+    /// 创建新的计算器
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 累加数据
+    pub fn add(&mut self, mut data: &[u8]) {
+        if self.odd {
+            if let Some((&low, rest)) = data.split_first() {
+                self.sum += low as u64;
+                self.odd = false;
+                data = rest;
+            }
+        }
+
+        let mut words = data.chunks_exact(4);
+        for word in &mut words {
+            self.sum += u32::from_be_bytes([word[0], word[1], word[2], word[3]]) as u64;
+        }
+        let rest = words.remainder();
+        let mut pairs = rest.chunks_exact(2);
+        for pair in &mut pairs {
+            self.sum += u16::from_be_bytes([pair[0], pair[1]]) as u64;
+        }
+        if let [high] = pairs.remainder() {
+            self.sum += (*high as u64) << 8;
+            self.odd = true;
+        }
+        self.sum = fold64(self.sum);
+    }
+
+    /// 累加MBuf中的数据
+    pub fn add_mbuf(&mut self, buf: &MBuf) {
+        self.add(buf);
+    }
+
+    /// 累加MChain中的所有数据
+    pub fn add_chain(&mut self, chain: &MChain) {
+        for seg in chain {
+            self.add(seg);
+        }
+    }
+
+    /// 累加MChain中指定范围的数据
+    ///
+    /// # Panics
+    ///
+    /// 范围超出链中数据时panic
+    pub fn add_chain_range(&mut self, chain: &MChain, range: impl RangeBounds<usize>) {
+        let (start, end) = bounds(range, chain.len());
+        for_each_range(chain, start, end, |data| self.add(data));
+    }
+
+    /// 获取折叠后未取反的16位和
+    pub fn sum(&self) -> u16 {
+        fold(self.sum)
+    }
+
+    /// 获取最终的校验和
+    pub fn finish(&self) -> u16 {
+        !self.sum()
+    }
+}
+
+/// 计算数据的互联网校验和
+pub fn internet_checksum(data: &[u8]) -> u16 {
+    let mut checksum = Checksum::new();
+    checksum.add(data);
+    checksum.finish()
+}
+
+/// 按RFC 1624在一个16位字段从old变为new后增量更新校验和：HC' = ~(~HC + ~m + m')
+pub fn update(checksum: u16, old: u16, new: u16) -> u16 {
+    !fold(!checksum as u64 + !old as u64 + new as u64)
+}
+
+/// 按RFC 1624在一段偶数偏移开始的数据从old变为new后增量更新校验和
+///
+/// # Panics
+///
+/// old与new长度不同时panic
+pub fn update_slice(checksum: u16, old: &[u8], new: &[u8]) -> u16 {
+    assert_eq!(old.len(), new.len(), "old and new fields differ in length");
+    let mut removed = Checksum::new();
+    removed.add(old);
+    let mut added = Checksum::new();
+    added.add(new);
+    !fold(!checksum as u64 + !removed.sum() as u64 + added.sum() as u64)
+}
+
+impl MBuf {
+    /// 计算数据的互联网校验和
+    pub fn internet_checksum(&self) -> u16 {
+        internet_checksum(self)
+    }
+
+    /// 计算数据的CRC32C
+    pub fn crc32c(&self) -> u32 {
+        crc32c(self)
+    }
+}
+
+impl MChain {
+    /// 计算链中数据的互联网校验和
+    pub fn internet_checksum(&self) -> u16 {
+        let mut checksum = Checksum::new();
+        checksum.add_chain(self);
+        checksum.finish()
+    }
+
+    /// 计算链中数据的CRC32C
+    pub fn crc32c(&self) -> u32 {
+        let mut crc = Crc32c::new();
+        crc.add_chain(self);
+        crc.finish()
+    }
+}
+
+/// 将64位累加和折叠到32位以内，避免溢出
+fn fold64(sum: u64) -> u64 {
+    let sum = (sum & 0xffff_ffff) + (sum >> 32);
+    (sum & 0xffff_ffff) + (sum >> 32)
+}
+
+/// 将累加和折叠为16位
+fn fold(mut sum: u64) -> u16 {
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    sum as u16
+}
+
+/// 依次访问链中[start, end)范围的数据
+fn for_each_range(chain: &MChain, start: usize, end: usize, mut f: impl FnMut(&[u8])) {
+    let mut pos = 0;
+    for seg in chain {
+        let seg_end = pos + seg.len();
+        if seg_end > start && pos < end {
+            f(&seg[start.saturating_sub(pos)..seg.len().min(end - pos)]);
+        }
+        if seg_end >= end {
+            break;
+        }
+        pos = seg_end;
+    }
+}
+
+/// CRC32C（Castagnoli）的增量计算器，支持时使用SSE4.2或ARMv8 CRC指令
+#[derive(Debug, Clone, Copy)]
+pub struct Crc32c {
+    state: u32,
+}
+
+impl Default for Crc32c {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Crc32c {
+    /// 创建新的计算器
+    pub fn new() -> Self {
+        Self { state: !0 }
+    }
+
+    /// 累加数据
+    pub fn add(&mut self, data: &[u8]) {
+        self.state = crc32c_update(self.state, data);
+    }
+
+    /// 累加MBuf中的数据
+    pub fn add_mbuf(&mut self, buf: &MBuf) {
+        self.add(buf);
+    }
+
+    /// 累加MChain中的所有数据
+    pub fn add_chain(&mut self, chain: &MChain) {
+        for seg in chain {
+            self.add(seg);
+        }
+    }
+
+    /// 累加MChain中指定范围的数据
+    ///
+    /// # Panics
+    ///
+    /// 范围超出链中数据时panic
+    pub fn add_chain_range(&mut self, chain: &MChain, range: impl RangeBounds<usize>) {
+        let (start, end) = bounds(range, chain.len());
+        for_each_range(chain, start, end, |data| self.add(data));
+    }
+
+    /// 获取最终的CRC值
+    pub fn finish(&self) -> u32 {
+        !self.state
+    }
+}
+
+/// 计算数据的CRC32C
+pub fn crc32c(data: &[u8]) -> u32 {
+    let mut crc = Crc32c::new();
+    crc.add(data);
+    crc.finish()
+}
+
+/// 反射形式的CRC32C多项式
+const POLY: u32 = 0x82f6_3b78;
+
+/// slicing-by-8查找表
+static TABLE: [[u32; 256]; 8] = make_table();
+
+const fn make_table() -> [[u32; 256]; 8] {
+    let mut table = [[0u32; 256]; 8];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ POLY } else { crc >> 1 };
+            bit += 1;
+        }
+        table[0][i] = crc;
+        i += 1;
+    }
+    let mut i = 0;
+    while i < 256 {
+        let mut t = 1;
+        while t < 8 {
+            let prev = table[t - 1][i];
+            table[t][i] = (prev >> 8) ^ table[0][(prev & 0xff) as usize];
+            t += 1;
+        }
+        i += 1;
+    }
+    table
+}
+
+fn crc32c_update(state: u32, data: &[u8]) -> u32 {
+    #[cfg(target_arch = "x86_64")]
+    {
+        if std::arch::is_x86_feature_detected!("sse4.2") {
+            return unsafe { crc32c_sse42(state, data) };
+        }
+    }
+    #[cfg(target_arch = "aarch64")]
+    {
+        if std::arch::is_aarch64_feature_detected!("crc") {
+            return unsafe { crc32c_armv8(state, data) };
+        }
+    }
+    crc32c_software(state, data)
+}
+
+/// 软件实现的CRC32C
+fn crc32c_software(mut state: u32, data: &[u8]) -> u32 {
+    let mut chunks = data.chunks_exact(8);
+    for chunk in &mut chunks {
+        let low = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]) ^ state;
+        let high = u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]);
+        state = TABLE[7][(low & 0xff) as usize]
+            ^ TABLE[6][((low >> 8) & 0xff) as usize]
+            ^ TABLE[5][((low >> 16) & 0xff) as usize]
+            ^ TABLE[4][(low >> 24) as usize]
+            ^ TABLE[3][(high & 0xff) as usize]
+            ^ TABLE[2][((high >> 8) & 0xff) as usize]
+            ^ TABLE[1][((high >> 16) & 0xff) as usize]
+            ^ TABLE[0][(high >> 24) as usize];
+    }
+    for &byte in chunks.remainder() {
+        state = (state >> 8) ^ TABLE[0][((state ^ byte as u32) & 0xff) as usize];
+    }
+    state
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "sse4.2")]
+unsafe fn crc32c_sse42(state: u32, data: &[u8]) -> u32 {
+    use std::arch::x86_64::{_mm_crc32_u64, _mm_crc32_u8};
+
+    let mut state = state as u64;
+    let mut chunks = data.chunks_exact(8);
+    for chunk in &mut chunks {
+        let word = u64::from_le_bytes(chunk.try_into().unwrap());
+        state = _mm_crc32_u64(state, word);
+    }
+    let mut state = state as u32;
+    for &byte in chunks.remainder() {
+        state = _mm_crc32_u8(state, byte);
+    }
+    state
+}
+
+#[cfg(target_arch = "aarch64")]
+#[target_feature(enable = "crc")]
+unsafe fn crc32c_armv8(mut state: u32, data: &[u8]) -> u32 {
+    use std::arch::aarch64::{__crc32cb, __crc32cd};
+
+    let mut chunks = data.chunks_exact(8);
+    for chunk in &mut chunks {
+        let word = u64::from_le_bytes(chunk.try_into().unwrap());
+        state = __crc32cd(state, word);
+    }
+    for &byte in chunks.remainder() {
+        state = __crc32cb(state, byte);
+    }
+    state
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mchain::chain_of;
+
+    #[test]
+    fn test_rfc1071_example() {
+        let data = [0x00, 0x01, 0xf2, 0x03, 0xf4, 0xf5, 0xf6, 0xf7];
+        let mut checksum = Checksum::new();
+        checksum.add(&data);
+        assert_eq!(checksum.sum(), 0xddf2);
+        assert_eq!(internet_checksum(&data), 0x220d);
+    }
+
+    #[test]
+    fn test_odd_segment_boundaries() {
+        let data: Vec<u8> = (0..=200).map(|i| (i * 7) as u8).collect();
+        let expected = internet_checksum(&data);
+        let chain = chain_of(&[&data[..3], &data[3..4], &data[4..101], &data[101..]]);
+        assert_eq!(chain.internet_checksum(), expected);
+
+        let mut range = Checksum::new();
+        range.add_chain_range(&chain, 3..150);
+        assert_eq!(range.finish(), internet_checksum(&data[3..150]));
+    }
+
+    #[test]
+    fn test_incremental_update() {
+        let mut header = [0x45, 0x00, 0x00, 0x54, 0x12, 0x34, 0x40, 0x00, 0x40, 0x01, 0x00, 0x00];
+        let checksum = internet_checksum(&header);
+
+        // TTL 0x40 -> 0x3f
+        let updated = update(checksum, 0x4001, 0x3f01);
+        header[8] = 0x3f;
+        assert_eq!(updated, internet_checksum(&header));
+
+        let updated = update_slice(updated, &header[4..6], &[0xab, 0xcd]);
+        header[4..6].copy_from_slice(&[0xab, 0xcd]);
+        assert_eq!(updated, internet_checksum(&header));
+    }
+
+    #[test]
+    fn test_crc32c() {
+        assert_eq!(crc32c(b"123456789"), 0xe306_9283);
+        assert_eq!(crc32c(&[0u8; 32]), 0x8a91_36aa);
+        assert_eq!(crc32c_software(!0, b"123456789"), !0xe306_9283);
+
+        let data: Vec<u8> = (0..1000u32).map(|i| (i * 31 % 251) as u8).collect();
+        for len in [0, 1, 7, 8, 9, 63, 1000] {
+            assert_eq!(crc32c_update(!0, &data[..len]), crc32c_software(!0, &data[..len]));
+        }
+
+        let chain = chain_of(&[&data[..5], &data[5..13], &data[13..]]);
+        assert_eq!(chain.crc32c(), crc32c(&data));
+        let mut crc = Crc32c::new();
+        crc.add_chain_range(&chain, 4..900);
+        assert_eq!(crc.finish(), crc32c(&data[4..900]));
+    }
+}
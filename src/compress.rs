@@ -0,0 +1,688 @@
+// MIT License
+//
+// Copyright (c) 2023 gaosg
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! 压缩和解压缩到从MPool分配的缓冲区链
+//!
+//! `compress`/`decompress`处理整个`Read`，例如`Cursor::new(&buf)`、`chain.cursor()`或网络流；
+//! `Compressor`/`Decompressor`接受陆续到达的数据块，随时可以取出已产生的输出。
+//! 输出按池中缓冲区的容量分段，解压缩时限制输出总长度以防御压缩炸弹
+
+use std::io::{self, Read, Write};
+use super::{MBuf, MChain, MPool};
+use super::mbuf_pool::AllocSite;
+
+/// 压缩算法
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    /// 原始deflate流（RFC 1951）
+    #[cfg(feature = "deflate")]
+    Deflate,
+    /// gzip格式（RFC 1952）
+    #[cfg(feature = "gzip")]
+    Gzip,
+    /// LZ4帧格式
+    #[cfg(feature = "lz4")]
+    Lz4,
+    /// zstd帧格式
+    #[cfg(feature = "zstd")]
+    Zstd,
+}
+
+/// 缓冲区容量为0时每次分配的大小
+const MIN_SEGMENT: usize = 4096;
+
+fn limit_exceeded(limit: usize) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("output exceeds limit of {} bytes", limit))
+}
+
+/// 把写入的数据追加到缓冲区链的写入器，缓冲区从池中分配，可以限制总长度
+///
+/// 启用`tracking`特性时，写入器分配的缓冲区都记录为创建写入器的位置
+pub struct ChainWriter<'p> {
+    pool: &'p MPool,
+    chain: MChain,
+    current: Option<MBuf>,
+    len: usize,
+    limit: usize,
+    site: AllocSite,
+}
+
+impl<'p> ChainWriter<'p> {
+    /// 创建写入器，缓冲区从pool中分配
+    #[cfg_attr(feature = "tracking", track_caller)]
+    pub fn new(pool: &'p MPool) -> Self {
+        Self {
+            pool,
+            chain: MChain::new(),
+            current: None,
+            len: 0,
+            limit: usize::MAX,
+            site: AllocSite::caller(),
+        }
+    }
+
+    /// 设置输出总长度的上限，超过时写入返回`InvalidData`错误
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    /// 已写入的字节数
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// 是否还没有写入数据
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// 取出到目前为止写入的数据，之后的写入从新的缓冲区开始，长度上限仍按总长度计算
+    pub fn take_chain(&mut self) -> MChain {
+        if let Some(buf) = self.current.take() {
+            self.push(buf);
+        }
+        std::mem::take(&mut self.chain)
+    }
+
+    /// 取出已写入的数据
+    pub fn into_chain(mut self) -> MChain {
+        self.take_chain()
+    }
+
+    /// 从reader读取直到结尾，数据直接读入缓冲区的空闲空间，返回读取的字节数
+    pub fn read_from<R: Read + ?Sized>(&mut self, reader: &mut R) -> io::Result<usize> {
+        let start = self.len;
+        loop {
+            // 多读一个字节用于发现超出上限
+            let room = self.limit - self.len;
+            let current = self.writable();
+            let spare = current.spare_capacity_mut();
+            let n = spare.len().min(room.saturating_add(1));
+            let n = match reader.read(&mut spare[..n]) {
+                Ok(0) => return Ok(self.len - start),
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            if n > room {
+                return Err(limit_exceeded(self.limit));
+            }
+            current.commit(n);
+            self.len += n;
+        }
+    }
+
+    /// 获取有空闲空间的当前缓冲区，已满时换一个新的
+    fn writable(&mut self) -> &mut MBuf {
+        if self.current.as_ref().is_some_and(|buf| buf.tailroom() == 0) {
+            let full = self.current.take().unwrap();
+            self.push(full);
+        }
+        self.current.get_or_insert_with(|| {
+            let mut buf = self.pool.alloc_at(self.site);
+            if buf.tailroom() == 0 {
+                buf.reserve(MIN_SEGMENT);
+            }
+            buf
+        })
+    }
+
+    fn push(&mut self, buf: MBuf) {
+        if buf.is_empty() {
+            self.pool.free(buf);
+        } else {
+            self.chain.push_back(buf);
+        }
+    }
+}
+
+impl Write for ChainWriter<'_> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        if data.len() > self.limit - self.len {
+            return Err(limit_exceeded(self.limit));
+        }
+        let spare = self.writable().spare_capacity_mut();
+        let n = spare.len().min(data.len());
+        spare[..n].copy_from_slice(&data[..n]);
+        self.current.as_mut().unwrap().commit(n);
+        self.len += n;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+enum EncoderKind<'p> {
+    #[cfg(feature = "deflate")]
+    Deflate(flate2::write::DeflateEncoder<ChainWriter<'p>>),
+    #[cfg(feature = "gzip")]
+    Gzip(flate2::write::GzEncoder<ChainWriter<'p>>),
+    #[cfg(feature = "lz4")]
+    Lz4(lz4_flex::frame::FrameEncoder<ChainWriter<'p>>),
+    #[cfg(feature = "zstd")]
+    Zstd(zstd::stream::write::Encoder<'static, ChainWriter<'p>>),
+}
+
+/// 增量压缩器，通过`Write`写入原始数据，压缩结果存放在从池中分配的缓冲区链中
+pub struct Compressor<'p> {
+    inner: EncoderKind<'p>,
+}
+
+impl<'p> Compressor<'p> {
+    /// 创建压缩器，输出缓冲区从pool中分配
+    #[cfg_attr(feature = "tracking", track_caller)]
+    pub fn new(algorithm: Algorithm, pool: &'p MPool) -> io::Result<Self> {
+        let out = ChainWriter::new(pool);
+        let inner = match algorithm {
+            #[cfg(feature = "deflate")]
+            Algorithm::Deflate => EncoderKind::Deflate(flate2::write::DeflateEncoder::new(out, flate2::Compression::default())),
+            #[cfg(feature = "gzip")]
+            Algorithm::Gzip => EncoderKind::Gzip(flate2::write::GzEncoder::new(out, flate2::Compression::default())),
+            #[cfg(feature = "lz4")]
+            Algorithm::Lz4 => EncoderKind::Lz4(lz4_flex::frame::FrameEncoder::new(out)),
+            #[cfg(feature = "zstd")]
+            Algorithm::Zstd => EncoderKind::Zstd(zstd::stream::write::Encoder::new(out, zstd::DEFAULT_COMPRESSION_LEVEL)?),
+        };
+        Ok(Self { inner })
+    }
+
+    /// 取出到目前为止产生的压缩数据，编码器内部缓存的部分要等到之后的写入或`finish`才输出
+    pub fn take_output(&mut self) -> MChain {
+        match &mut self.inner {
+            #[cfg(feature = "deflate")]
+            EncoderKind::Deflate(encoder) => encoder.get_mut().take_chain(),
+            #[cfg(feature = "gzip")]
+            EncoderKind::Gzip(encoder) => encoder.get_mut().take_chain(),
+            #[cfg(feature = "lz4")]
+            EncoderKind::Lz4(encoder) => encoder.get_mut().take_chain(),
+            #[cfg(feature = "zstd")]
+            EncoderKind::Zstd(encoder) => encoder.get_mut().take_chain(),
+        }
+    }
+
+    /// 结束压缩流，返回尚未取出的压缩数据
+    pub fn finish(self) -> io::Result<MChain> {
+        let out = match self.inner {
+            #[cfg(feature = "deflate")]
+            EncoderKind::Deflate(encoder) => encoder.finish()?,
+            #[cfg(feature = "gzip")]
+            EncoderKind::Gzip(encoder) => encoder.finish()?,
+            #[cfg(feature = "lz4")]
+            EncoderKind::Lz4(encoder) => encoder.finish().map_err(io::Error::other)?,
+            #[cfg(feature = "zstd")]
+            EncoderKind::Zstd(encoder) => encoder.finish()?,
+        };
+        Ok(out.into_chain())
+    }
+}
+
+impl Write for Compressor<'_> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        match &mut self.inner {
+            #[cfg(feature = "deflate")]
+            EncoderKind::Deflate(encoder) => encoder.write(data),
+            #[cfg(feature = "gzip")]
+            EncoderKind::Gzip(encoder) => encoder.write(data),
+            #[cfg(feature = "lz4")]
+            EncoderKind::Lz4(encoder) => encoder.write(data),
+            #[cfg(feature = "zstd")]
+            EncoderKind::Zstd(encoder) => encoder.write(data),
+        }
+    }
+
+    /// 把编码器缓存的数据压缩输出，会降低压缩率
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.inner {
+            #[cfg(feature = "deflate")]
+            EncoderKind::Deflate(encoder) => encoder.flush(),
+            #[cfg(feature = "gzip")]
+            EncoderKind::Gzip(encoder) => encoder.flush(),
+            #[cfg(feature = "lz4")]
+            EncoderKind::Lz4(encoder) => encoder.flush(),
+            #[cfg(feature = "zstd")]
+            EncoderKind::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+enum DecoderKind<'p> {
+    #[cfg(feature = "deflate")]
+    Deflate(Inflate<'p>),
+    #[cfg(feature = "gzip")]
+    Gzip(flate2::write::GzDecoder<ChainWriter<'p>>),
+    #[cfg(feature = "lz4")]
+    Lz4(Box<lz4::PushDecoder<'p>>),
+    #[cfg(feature = "zstd")]
+    Zstd(zstd::stream::zio::Writer<ChainWriter<'p>, zstd::stream::raw::Decoder<'static>>),
+}
+
+/// 增量解压缩器，通过`Write`写入陆续到达的压缩数据，解出的数据存放在从池中分配的缓冲区链中
+pub struct Decompressor<'p> {
+    inner: DecoderKind<'p>,
+}
+
+impl<'p> Decompressor<'p> {
+    /// 创建解压缩器，输出缓冲区从pool中分配，输出超过max_output字节时写入返回`InvalidData`错误
+    #[cfg_attr(feature = "tracking", track_caller)]
+    pub fn new(algorithm: Algorithm, pool: &'p MPool, max_output: usize) -> io::Result<Self> {
+        let out = ChainWriter::new(pool).with_limit(max_output);
+        let inner = match algorithm {
+            #[cfg(feature = "deflate")]
+            Algorithm::Deflate => DecoderKind::Deflate(Inflate {
+                state: flate2::Decompress::new(false),
+                done: false,
+                out,
+            }),
+            #[cfg(feature = "gzip")]
+            Algorithm::Gzip => DecoderKind::Gzip(flate2::write::GzDecoder::new(out)),
+            #[cfg(feature = "lz4")]
+            Algorithm::Lz4 => DecoderKind::Lz4(Box::new(lz4::PushDecoder::new(out))),
+            #[cfg(feature = "zstd")]
+            Algorithm::Zstd => DecoderKind::Zstd(zstd::stream::zio::Writer::new(out, zstd::stream::raw::Decoder::new()?)),
+        };
+        Ok(Self { inner })
+    }
+
+    /// 取出到目前为止解出的数据
+    pub fn take_output(&mut self) -> MChain {
+        match &mut self.inner {
+            #[cfg(feature = "deflate")]
+            DecoderKind::Deflate(decoder) => decoder.out.take_chain(),
+            #[cfg(feature = "gzip")]
+            DecoderKind::Gzip(decoder) => decoder.get_mut().take_chain(),
+            #[cfg(feature = "lz4")]
+            DecoderKind::Lz4(decoder) => decoder.out.take_chain(),
+            #[cfg(feature = "zstd")]
+            DecoderKind::Zstd(decoder) => decoder.writer_mut().take_chain(),
+        }
+    }
+
+    /// 结束解压缩，返回尚未取出的数据；压缩流不完整时返回错误
+    pub fn finish(self) -> io::Result<MChain> {
+        let out = match self.inner {
+            #[cfg(feature = "deflate")]
+            DecoderKind::Deflate(decoder) => {
+                if !decoder.done {
+                    return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "truncated deflate stream"));
+                }
+                decoder.out
+            }
+            #[cfg(feature = "gzip")]
+            DecoderKind::Gzip(decoder) => decoder.finish()?,
+            #[cfg(feature = "lz4")]
+            DecoderKind::Lz4(decoder) => decoder.finish()?,
+            #[cfg(feature = "zstd")]
+            DecoderKind::Zstd(mut decoder) => {
+                decoder.finish()?;
+                decoder.into_inner().0
+            }
+        };
+        Ok(out.into_chain())
+    }
+}
+
+impl Write for Decompressor<'_> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        match &mut self.inner {
+            #[cfg(feature = "deflate")]
+            DecoderKind::Deflate(decoder) => decoder.write(data),
+            #[cfg(feature = "gzip")]
+            DecoderKind::Gzip(decoder) => decoder.write(data),
+            #[cfg(feature = "lz4")]
+            DecoderKind::Lz4(decoder) => decoder.write(data),
+            #[cfg(feature = "zstd")]
+            DecoderKind::Zstd(decoder) => decoder.write(data),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.inner {
+            #[cfg(feature = "deflate")]
+            DecoderKind::Deflate(_) => Ok(()),
+            #[cfg(feature = "gzip")]
+            DecoderKind::Gzip(decoder) => decoder.flush(),
+            #[cfg(feature = "lz4")]
+            DecoderKind::Lz4(_) => Ok(()),
+            #[cfg(feature = "zstd")]
+            DecoderKind::Zstd(decoder) => decoder.flush(),
+        }
+    }
+}
+
+/// flate2的deflate写入解码器不报告流是否结束，这里直接驱动`Decompress`
+#[cfg(feature = "deflate")]
+struct Inflate<'p> {
+    state: flate2::Decompress,
+    done: bool,
+    out: ChainWriter<'p>,
+}
+
+#[cfg(feature = "deflate")]
+impl Write for Inflate<'_> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        if self.done {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "data after end of deflate stream"));
+        }
+        let mut input = InflateInput { state: &mut self.state, data, done: &mut self.done };
+        self.out.read_from(&mut input)?;
+        if !input.data.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "data after end of deflate stream"));
+        }
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// 从本次写入的数据中解出输出，返回0表示需要更多输入或流已结束
+#[cfg(feature = "deflate")]
+struct InflateInput<'a> {
+    state: &'a mut flate2::Decompress,
+    data: &'a [u8],
+    done: &'a mut bool,
+}
+
+#[cfg(feature = "deflate")]
+impl Read for InflateInput<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while !*self.done {
+            let (total_in, total_out) = (self.state.total_in(), self.state.total_out());
+            let status = self
+                .state
+                .decompress(self.data, buf, flate2::FlushDecompress::None)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            let consumed = (self.state.total_in() - total_in) as usize;
+            let produced = (self.state.total_out() - total_out) as usize;
+            self.data = &self.data[consumed..];
+            *self.done = status == flate2::Status::StreamEnd;
+            if produced > 0 {
+                return Ok(produced);
+            }
+            if consumed == 0 {
+                break;
+            }
+        }
+        Ok(0)
+    }
+}
+
+/// lz4_flex只提供基于`Read`的帧解码器，这里只把完整的帧头或数据块交给它，
+/// 未到齐的部分留到下次写入
+#[cfg(feature = "lz4")]
+mod lz4 {
+    use std::io::{self, Read, Write};
+    use lz4_flex::frame::FrameDecoder;
+    use super::ChainWriter;
+
+    const MAGIC: [u8; 4] = 0x184D2204u32.to_le_bytes();
+    const FLG_BLOCK_CHECKSUM: u8 = 0x10;
+    const FLG_CONTENT_SIZE: u8 = 0x08;
+    const FLG_CONTENT_CHECKSUM: u8 = 0x04;
+    const FLG_DICTIONARY_ID: u8 = 0x01;
+
+    /// 下一个完整单元的类型
+    enum Unit {
+        Header,
+        Block { block_checksum: bool, content_checksum: bool },
+        /// 不是标准帧，全部交给FrameDecoder报错
+        Raw,
+    }
+
+    /// 只暴露到最后一个完整单元结尾的输入
+    struct Input {
+        data: Vec<u8>,
+        pos: usize,
+        ready: usize,
+        unit: Unit,
+    }
+
+    impl Input {
+        fn push(&mut self, bytes: &[u8]) {
+            if self.pos == self.data.len() {
+                self.data.clear();
+                self.pos = 0;
+                self.ready = 0;
+            }
+            self.data.extend_from_slice(bytes);
+            self.scan();
+        }
+
+        /// 推进ready到最后一个完整单元之后
+        fn scan(&mut self) {
+            loop {
+                let avail = &self.data[self.ready..];
+                let need = match self.unit {
+                    Unit::Raw => avail.len(),
+                    Unit::Header => {
+                        if avail.len() >= 4 && avail[..4] != MAGIC {
+                            self.unit = Unit::Raw;
+                            continue;
+                        }
+                        let Some(&flg) = avail.get(4) else { return };
+                        7 + if flg & FLG_CONTENT_SIZE != 0 { 8 } else { 0 } + if flg & FLG_DICTIONARY_ID != 0 { 4 } else { 0 }
+                    }
+                    Unit::Block { block_checksum, content_checksum } => {
+                        let Some(size) = avail.get(..4) else { return };
+                        match u32::from_le_bytes(size.try_into().unwrap()) {
+                            0 => 4 + if content_checksum { 4 } else { 0 },
+                            size => 4 + (size & 0x7fff_ffff) as usize + if block_checksum { 4 } else { 0 },
+                        }
+                    }
+                };
+                if need == 0 || avail.len() < need {
+                    return;
+                }
+                self.unit = match self.unit {
+                    Unit::Header => Unit::Block {
+                        block_checksum: avail[4] & FLG_BLOCK_CHECKSUM != 0,
+                        content_checksum: avail[4] & FLG_CONTENT_CHECKSUM != 0,
+                    },
+                    Unit::Block { .. } if avail[..4] == [0; 4] => Unit::Header,
+                    Unit::Block { block_checksum, content_checksum } => Unit::Block { block_checksum, content_checksum },
+                    Unit::Raw => Unit::Raw,
+                };
+                self.ready += need;
+            }
+        }
+    }
+
+    impl Read for Input {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = buf.len().min(self.ready - self.pos);
+            buf[..n].copy_from_slice(&self.data[self.pos..self.pos + n]);
+            self.pos += n;
+            Ok(n)
+        }
+    }
+
+    pub(super) struct PushDecoder<'p> {
+        decoder: FrameDecoder<Input>,
+        pub(super) out: ChainWriter<'p>,
+    }
+
+    impl<'p> PushDecoder<'p> {
+        pub(super) fn new(out: ChainWriter<'p>) -> Self {
+            let input = Input { data: Vec::new(), pos: 0, ready: 0, unit: Unit::Header };
+            Self { decoder: FrameDecoder::new(input), out }
+        }
+
+        pub(super) fn finish(self) -> io::Result<ChainWriter<'p>> {
+            let input = self.decoder.get_ref();
+            if input.pos != input.data.len() || !matches!(input.unit, Unit::Header) {
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "truncated lz4 frame"));
+            }
+            Ok(self.out)
+        }
+    }
+
+    impl Write for PushDecoder<'_> {
+        fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+            self.decoder.get_mut().push(data);
+            // 每个帧结束时FrameDecoder返回0，继续解码后面已到齐的帧
+            loop {
+                let pos = self.decoder.get_ref().pos;
+                self.out.read_from(&mut self.decoder)?;
+                let input = self.decoder.get_ref();
+                if input.pos == input.ready || input.pos == pos {
+                    break;
+                }
+            }
+            Ok(data.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+}
+
+/// 压缩input中的全部数据，结果存放在从pool分配的缓冲区链中
+#[cfg_attr(feature = "tracking", track_caller)]
+pub fn compress<R: Read>(algorithm: Algorithm, mut input: R, pool: &MPool) -> io::Result<MChain> {
+    let mut compressor = Compressor::new(algorithm, pool)?;
+    io::copy(&mut input, &mut compressor)?;
+    compressor.finish()
+}
+
+/// 解压缩input，结果存放在从pool分配的缓冲区链中，输出超过max_output字节时返回`InvalidData`错误
+#[cfg_attr(feature = "tracking", track_caller)]
+pub fn decompress<R: Read>(algorithm: Algorithm, input: R, pool: &MPool, max_output: usize) -> io::Result<MChain> {
+    let mut out = ChainWriter::new(pool).with_limit(max_output);
+    match algorithm {
+        #[cfg(feature = "deflate")]
+        Algorithm::Deflate => out.read_from(&mut flate2::read::DeflateDecoder::new(input))?,
+        #[cfg(feature = "gzip")]
+        Algorithm::Gzip => out.read_from(&mut flate2::read::GzDecoder::new(input))?,
+        #[cfg(feature = "lz4")]
+        Algorithm::Lz4 => out.read_from(&mut lz4_flex::frame::FrameDecoder::new(input))?,
+        #[cfg(feature = "zstd")]
+        Algorithm::Zstd => out.read_from(&mut zstd::stream::read::Decoder::new(input)?)?,
+    };
+    Ok(out.into_chain())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Cursor;
+    use crate::mchain::chain_of;
+
+    fn algorithms() -> Vec<Algorithm> {
+        vec![
+            #[cfg(feature = "deflate")]
+            Algorithm::Deflate,
+            #[cfg(feature = "gzip")]
+            Algorithm::Gzip,
+            #[cfg(feature = "lz4")]
+            Algorithm::Lz4,
+            #[cfg(feature = "zstd")]
+            Algorithm::Zstd,
+        ]
+    }
+
+    #[test]
+    fn test_chain_writer() {
+        let pool = MPool::new(0, 4);
+        let mut writer = ChainWriter::new(&pool).with_limit(10);
+        writer.write_all(b"0123456").unwrap();
+        assert!(writer.write_all(b"7890").is_err());
+        assert_eq!(writer.read_from(&mut &b"789"[..]).unwrap(), 3);
+        assert!(writer.read_from(&mut &b"x"[..]).is_err());
+
+        let chain = writer.into_chain();
+        assert_eq!(chain.to_vec(), b"0123456789");
+        assert_eq!(chain.segment_count(), 3);
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let pool = MPool::new(0, 256);
+        let text: Vec<u8> = b"pooled buffers compress well. ".iter().copied().cycle().take(10_000).collect();
+        let input = chain_of(&text.chunks(1000).collect::<Vec<_>>());
+
+        for algorithm in algorithms() {
+            let packed = compress(algorithm, input.cursor(), &pool).unwrap();
+            assert!(packed.len() < text.len() / 10, "{:?}", algorithm);
+            assert!(packed.segments().all(|seg| seg.capacity() == 256));
+
+            let unpacked = decompress(algorithm, packed.cursor(), &pool, text.len()).unwrap();
+            assert_eq!(unpacked.to_vec(), text);
+
+            // 解压后超过上限
+            assert!(decompress(algorithm, packed.cursor(), &pool, text.len() - 1).is_err());
+        }
+    }
+
+    #[test]
+    fn test_incremental() {
+        let pool = MPool::new(0, 64);
+        let text: Vec<u8> = (0..20_000u32).flat_map(|i| (i % 251).to_le_bytes()).collect();
+
+        for algorithm in algorithms() {
+            let mut compressor = Compressor::new(algorithm, &pool).unwrap();
+            let mut packed = MChain::new();
+            for chunk in text.chunks(3000) {
+                compressor.write_all(chunk).unwrap();
+                packed.append(&mut compressor.take_output());
+            }
+            packed.append(&mut compressor.finish().unwrap());
+            let packed = packed.to_vec();
+
+            // 按很小的块喂入，边写边取出
+            let mut decompressor = Decompressor::new(algorithm, &pool, text.len()).unwrap();
+            let mut unpacked = Vec::new();
+            for chunk in packed.chunks(7) {
+                decompressor.write_all(chunk).unwrap();
+                unpacked.extend(decompressor.take_output().to_vec());
+            }
+            assert!(!unpacked.is_empty(), "{:?}", algorithm);
+            unpacked.extend(decompressor.finish().unwrap().to_vec());
+            assert_eq!(unpacked, text, "{:?}", algorithm);
+
+            let mut decompressor = Decompressor::new(algorithm, &pool, text.len() - 1).unwrap();
+            assert!(decompressor.write_all(&packed).and_then(|_| decompressor.finish().map(drop)).is_err());
+
+            // 不完整的压缩流
+            let mut decompressor = Decompressor::new(algorithm, &pool, text.len()).unwrap();
+            decompressor.write_all(&packed[..packed.len() / 2]).unwrap();
+            assert!(decompressor.finish().is_err(), "{:?}", algorithm);
+        }
+    }
+
+    #[test]
+    fn test_single_buffer() {
+        let pool = MPool::new(0, 64);
+        let mut buf = MBuf::with_capacity(16);
+        buf.append(b"single buffer");
+        for algorithm in algorithms() {
+            let packed = compress(algorithm, Cursor::new(&buf), &pool).unwrap();
+            let unpacked = decompress(algorithm, packed.cursor(), &pool, 64).unwrap();
+            assert_eq!(unpacked.to_vec(), b"single buffer");
+            assert!(decompress(algorithm, &b"not compressed data"[..], &pool, 64).is_err());
+        }
+    }
+}
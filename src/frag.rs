@@ -0,0 +1,312 @@
+// MIT License
+//
+// Copyright (c) 2023 gaosg
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::collections::{hash_map, BTreeMap, HashMap};
+use std::fmt;
+use std::hash::Hash;
+use std::ops::Range;
+use std::time::{Duration, Instant};
+use super::{MChain, MPool};
+
+impl MChain {
+    /// 按mtu把链切分为多个分片，每个分片是原链的零拷贝切片
+    ///
+    /// # Panics
+    ///
+    /// mtu为0时panic
+    pub fn fragment(&self, mtu: usize) -> Vec<MChain> {
+        assert!(mtu > 0, "mtu must be non-zero");
+        (0..self.len())
+            .step_by(mtu)
+            .map(|start| self.slice(start..self.len().min(start + mtu)))
+            .collect()
+    }
+
+    /// 按mtu切分，并在每个分片前加入一个从池中分配、预留headroom字节的空分段，
+    /// 之后可以通过`prepend`写入分片头；每个分片的负载为mtu - headroom字节
+    ///
+    /// # Panics
+    ///
+    /// headroom不小于mtu时panic
+    #[cfg_attr(feature = "tracking", track_caller)]
+    pub fn fragment_with_headroom(&self, mtu: usize, pool: &MPool, headroom: usize) -> Vec<MChain> {
+        assert!(headroom < mtu, "headroom {} leaves no room for payload in mtu {}", headroom, mtu);
+        let mut frags = self.fragment(mtu - headroom);
+        for frag in &mut frags {
+            let mut head = pool.alloc();
+            head.reserve_headroom(headroom);
+            frag.push_front(head);
+        }
+        frags
+    }
+}
+
+/// 分片重组错误
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReassemblyError {
+    /// 分片与已收到的分片部分重叠，整个报文被丢弃
+    Overlap,
+    /// 分片与已知的报文总长度矛盾，整个报文被丢弃
+    Conflict,
+    /// 报文超过允许的最大长度
+    TooLarge,
+}
+
+impl fmt::Display for ReassemblyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReassemblyError::Overlap => write!(f, "fragment overlaps previously received data"),
+            ReassemblyError::Conflict => write!(f, "fragment conflicts with the datagram length"),
+            ReassemblyError::TooLarge => write!(f, "reassembled datagram exceeds the size limit"),
+        }
+    }
+}
+
+impl std::error::Error for ReassemblyError {}
+
+/// 正在重组的报文
+struct Entry {
+    frags: BTreeMap<usize, MChain>,
+    total: Option<usize>,
+    received: usize,
+    first_seen: Instant,
+}
+
+impl Entry {
+    fn new(now: Instant) -> Self {
+        Self {
+            frags: BTreeMap::new(),
+            total: None,
+            received: 0,
+            first_seen: now,
+        }
+    }
+
+    /// 尚未收到的数据区间
+    fn missing(&self) -> Vec<Range<usize>> {
+        let mut gaps = Vec::new();
+        let mut pos = 0;
+        for (&offset, frag) in &self.frags {
+            if offset > pos {
+                gaps.push(pos..offset);
+            }
+            pos = offset + frag.len();
+        }
+        match self.total {
+            Some(total) if total > pos => gaps.push(pos..total),
+            None => gaps.push(pos..usize::MAX),
+            _ => {}
+        }
+        gaps
+    }
+}
+
+/// 按(id, offset)接收乱序分片并重组为完整MChain的重组器
+pub struct Reassembler<K> {
+    entries: HashMap<K, Entry>,
+    timeout: Duration,
+    max_size: usize,
+}
+
+impl<K: Hash + Eq> Reassembler<K> {
+    /// 创建重组器，超过timeout仍未完成的报文会被丢弃
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            entries: HashMap::new(),
+            timeout,
+            max_size: usize::MAX,
+        }
+    }
+
+    /// 限制重组后报文的最大长度
+    pub fn with_max_size(mut self, max_size: usize) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// 加入一个分片，more表示之后还有分片；报文完整时返回重组后的MChain
+    ///
+    /// 完全重复的分片被忽略；部分重叠或与报文长度矛盾时丢弃整个报文并返回错误
+    pub fn insert(&mut self, id: K, offset: usize, more: bool, frag: MChain, now: Instant) -> Result<Option<MChain>, ReassemblyError> {
+        let end = match offset.checked_add(frag.len()) {
+            Some(end) if end <= self.max_size => end,
+            _ => {
+                self.entries.remove(&id);
+                return Err(ReassemblyError::TooLarge);
+            }
+        };
+
+        let timeout = self.timeout;
+        let mut slot = match self.entries.entry(id) {
+            hash_map::Entry::Occupied(mut occupied) => {
+                // 旧报文已超时，重新开始重组
+                if now.saturating_duration_since(occupied.get().first_seen) >= timeout {
+                    occupied.insert(Entry::new(now));
+                }
+                occupied
+            }
+            hash_map::Entry::Vacant(vacant) => vacant.insert_entry(Entry::new(now)),
+        };
+
+        match Self::add(slot.get_mut(), offset, end, more, frag) {
+            Err(err) => {
+                slot.remove();
+                Err(err)
+            }
+            Ok(false) => Ok(None),
+            Ok(true) => {
+                let mut chain = MChain::new();
+                for (_, mut frag) in slot.remove().frags {
+                    chain.append(&mut frag);
+                }
+                Ok(Some(chain))
+            }
+        }
+    }
+
+    /// 丢弃超时的报文，返回丢弃的数量
+    pub fn expire(&mut self, now: Instant) -> usize {
+        let before = self.entries.len();
+        let timeout = self.timeout;
+        self.entries
+            .retain(|_, entry| now.saturating_duration_since(entry.first_seen) < timeout);
+        before - self.entries.len()
+    }
+
+    /// 获取报文尚未收到的数据区间，总长度未知时最后一个区间以usize::MAX结尾
+    pub fn missing(&self, id: &K) -> Option<Vec<Range<usize>>> {
+        self.entries.get(id).map(Entry::missing)
+    }
+
+    /// 获取正在重组的报文数量
+    pub fn pending(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// 把分片加入报文，返回报文是否已完整
+    fn add(entry: &mut Entry, offset: usize, end: usize, more: bool, frag: MChain) -> Result<bool, ReassemblyError> {
+        if !more {
+            match entry.total {
+                Some(total) if total != end => return Err(ReassemblyError::Conflict),
+                _ => entry.total = Some(end),
+            }
+            if entry.frags.iter().next_back().is_some_and(|(&start, last)| start + last.len() > end) {
+                return Err(ReassemblyError::Conflict);
+            }
+        } else if entry.total.is_some_and(|total| end > total) {
+            return Err(ReassemblyError::Conflict);
+        }
+
+        if let Some(existing) = entry.frags.get(&offset) {
+            if existing.len() == frag.len() {
+                return Ok(false);
+            }
+            return Err(ReassemblyError::Overlap);
+        }
+        let prev_overlaps = entry
+            .frags
+            .range(..offset)
+            .next_back()
+            .is_some_and(|(&start, prev)| start + prev.len() > offset);
+        let next_overlaps = entry.frags.range(offset..).next().is_some_and(|(&start, _)| start < end);
+        if prev_overlaps || next_overlaps {
+            return Err(ReassemblyError::Overlap);
+        }
+
+        entry.received += frag.len();
+        if !frag.is_empty() {
+            entry.frags.insert(offset, frag);
+        }
+        Ok(entry.total == Some(entry.received))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MBuf;
+
+    fn payload(len: usize) -> MChain {
+        let mut buf = MBuf::with_capacity(len);
+        buf.append(&(0..len).map(|i| i as u8).collect::<Vec<_>>());
+        MChain::from(buf)
+    }
+
+    #[test]
+    fn test_fragment() {
+        let chain = payload(10);
+        let frags = chain.fragment(4);
+        let lens: Vec<usize> = frags.iter().map(MChain::len).collect();
+        assert_eq!(lens, vec![4, 4, 2]);
+        assert_eq!(frags[1].front().unwrap().as_ptr(), chain.front().unwrap()[4..].as_ptr());
+
+        let pool = MPool::new(0, 16);
+        let mut frags = chain.fragment_with_headroom(6, &pool, 2);
+        assert_eq!(frags.len(), 3);
+        let head = frags[0].pop_front().unwrap();
+        assert_eq!(head.headroom(), 2);
+        assert!(head.is_empty());
+    }
+
+    #[test]
+    fn test_reassemble_out_of_order() {
+        let chain = payload(10);
+        let now = Instant::now();
+        let mut reassembler = Reassembler::new(Duration::from_secs(1));
+        let frags = chain.fragment(3);
+
+        assert!(matches!(reassembler.insert(7u32, 9, false, frags[3].clone(), now), Ok(None)));
+        assert!(matches!(reassembler.insert(7, 0, true, frags[0].clone(), now), Ok(None)));
+        assert_eq!(reassembler.missing(&7).unwrap(), vec![Range { start: 3, end: 9 }]);
+        assert!(matches!(reassembler.insert(7, 0, true, frags[0].clone(), now), Ok(None)));
+        assert!(matches!(reassembler.insert(7, 6, true, frags[2].clone(), now), Ok(None)));
+
+        let whole = reassembler.insert(7, 3, true, frags[1].clone(), now).unwrap().unwrap();
+        assert_eq!(whole.to_vec(), chain.to_vec());
+        assert_eq!(reassembler.pending(), 0);
+    }
+
+    #[test]
+    fn test_reassemble_errors() {
+        let chain = payload(10);
+        let now = Instant::now();
+        let mut reassembler = Reassembler::new(Duration::from_secs(1)).with_max_size(8);
+
+        reassembler.insert(1u32, 0, true, chain.slice(..4), now).unwrap();
+        assert!(matches!(reassembler.insert(1, 2, true, chain.slice(2..6), now), Err(ReassemblyError::Overlap)));
+        assert_eq!(reassembler.pending(), 0);
+
+        reassembler.insert(2, 4, false, chain.slice(4..6), now).unwrap();
+        assert!(matches!(reassembler.insert(2, 4, true, chain.slice(4..8), now), Err(ReassemblyError::Conflict)));
+        assert!(matches!(reassembler.insert(3, 4, true, chain.slice(..6), now), Err(ReassemblyError::TooLarge)));
+
+        // 偏移溢出与超长一样丢弃整个报文
+        reassembler.insert(5, 0, true, chain.slice(..2), now).unwrap();
+        assert!(matches!(reassembler.insert(5, usize::MAX, true, chain.slice(..2), now), Err(ReassemblyError::TooLarge)));
+        assert_eq!(reassembler.pending(), 0);
+
+        reassembler.insert(4, 0, true, chain.slice(..2), now).unwrap();
+        assert_eq!(reassembler.expire(now + Duration::from_millis(500)), 0);
+        assert_eq!(reassembler.expire(now + Duration::from_secs(1)), 1);
+        assert_eq!(reassembler.pending(), 0);
+    }
+}
@@ -0,0 +1,462 @@
+// MIT License
+//
+// Copyright (c) 2023 gaosg
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! 增量帧解码与编码：长度前缀、varint前缀、按行和任意分隔符
+//!
+//! 解码器从不断追加数据的`MBuf`中取出完整的帧，帧与输入缓冲区共享内存
+
+use std::io::{self, Write};
+use std::ops::Range;
+use super::MBuf;
+
+/// 在输入数据中找到的一帧
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    /// 帧内容在输入数据中的范围
+    pub payload: Range<usize>,
+    /// 取出该帧后应丢弃的字节数，包括前缀和分隔符
+    pub consumed: usize,
+}
+
+/// 帧解码器
+pub trait Decoder {
+    /// 在data开头查找一个完整的帧，数据不足时返回None
+    ///
+    /// 返回None时解码器可以记录已检查过的位置，下次以更长的data调用时继续
+    fn find_frame(&mut self, data: &[u8]) -> io::Result<Option<Frame>>;
+
+    /// 从buf开头取出一帧，帧与buf共享内存
+    fn decode(&mut self, buf: &mut MBuf) -> io::Result<Option<MBuf>> {
+        let Some(frame) = self.find_frame(buf)? else {
+            return Ok(None);
+        };
+        let payload = buf.slice(frame.payload);
+        buf.advance(frame.consumed);
+        Ok(Some(payload))
+    }
+}
+
+/// 帧编码器
+pub trait Encoder {
+    /// 将payload编码为一帧写入dst，dst可以是`MBuf`或任何`Write`
+    fn encode<W: Write>(&mut self, payload: &[u8], dst: &mut W) -> io::Result<()>;
+}
+
+fn frame_too_large(len: usize, max: usize) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("frame of {} bytes exceeds limit {}", len, max))
+}
+
+/// 默认的最大帧长度
+pub const DEFAULT_MAX_FRAME: usize = 8 * 1024 * 1024;
+
+/// 固定宽度长度前缀的帧
+///
+/// 帧长度 = 前缀值 + adjustment，前缀不计入帧内容
+#[derive(Debug, Clone)]
+pub struct LengthDelimited {
+    width: usize,
+    big_endian: bool,
+    adjustment: isize,
+    max_frame: usize,
+}
+
+impl LengthDelimited {
+    /// 创建width字节大端序长度前缀的编解码器
+    ///
+    /// # Panics
+    ///
+    /// width不是1、2、4、8之一时panic
+    pub fn new(width: usize) -> Self {
+        assert!(matches!(width, 1 | 2 | 4 | 8), "unsupported length prefix width {}", width);
+        Self {
+            width,
+            big_endian: true,
+            adjustment: 0,
+            max_frame: DEFAULT_MAX_FRAME,
+        }
+    }
+
+    /// 使用小端序前缀
+    pub fn little_endian(mut self) -> Self {
+        self.big_endian = false;
+        self
+    }
+
+    /// 设置前缀值到帧长度的修正量，例如前缀包含自身长度时为`-width`
+    pub fn with_adjustment(mut self, adjustment: isize) -> Self {
+        self.adjustment = adjustment;
+        self
+    }
+
+    /// 设置最大帧长度
+    pub fn with_max_frame(mut self, max_frame: usize) -> Self {
+        self.max_frame = max_frame;
+        self
+    }
+
+    fn read_prefix(&self, prefix: &[u8]) -> u64 {
+        let mut bytes = [0u8; 8];
+        if self.big_endian {
+            bytes[8 - self.width..].copy_from_slice(prefix);
+            u64::from_be_bytes(bytes)
+        } else {
+            bytes[..self.width].copy_from_slice(prefix);
+            u64::from_le_bytes(bytes)
+        }
+    }
+}
+
+impl Decoder for LengthDelimited {
+    fn find_frame(&mut self, data: &[u8]) -> io::Result<Option<Frame>> {
+        if data.len() < self.width {
+            return Ok(None);
+        }
+        let value = self.read_prefix(&data[..self.width]);
+        let len = usize::try_from(value)
+            .ok()
+            .and_then(|value| value.checked_add_signed(self.adjustment))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "invalid length prefix"))?;
+        if len > self.max_frame {
+            return Err(frame_too_large(len, self.max_frame));
+        }
+        let end = self.width + len;
+        if data.len() < end {
+            return Ok(None);
+        }
+        Ok(Some(Frame { payload: self.width..end, consumed: end }))
+    }
+}
+
+impl Encoder for LengthDelimited {
+    fn encode<W: Write>(&mut self, payload: &[u8], dst: &mut W) -> io::Result<()> {
+        if payload.len() > self.max_frame {
+            return Err(frame_too_large(payload.len(), self.max_frame));
+        }
+        let value = (payload.len() as u64)
+            .checked_add_signed(-(self.adjustment as i64))
+            .filter(|value| self.width == 8 || *value >> (self.width * 8) == 0)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "frame length does not fit prefix"))?;
+        if self.big_endian {
+            dst.write_all(&value.to_be_bytes()[8 - self.width..])?;
+        } else {
+            dst.write_all(&value.to_le_bytes()[..self.width])?;
+        }
+        dst.write_all(payload)?;
+        Ok(())
+    }
+}
+
+/// 无符号LEB128 varint长度前缀的帧
+#[derive(Debug, Clone)]
+pub struct VarintDelimited {
+    max_frame: usize,
+}
+
+impl VarintDelimited {
+    /// 创建使用默认最大帧长度的编解码器
+    pub fn new() -> Self {
+        Self { max_frame: DEFAULT_MAX_FRAME }
+    }
+
+    /// 设置最大帧长度
+    pub fn with_max_frame(mut self, max_frame: usize) -> Self {
+        self.max_frame = max_frame;
+        self
+    }
+}
+
+impl Default for VarintDelimited {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Decoder for VarintDelimited {
+    fn find_frame(&mut self, data: &[u8]) -> io::Result<Option<Frame>> {
+        let mut value: u64 = 0;
+        for (i, &byte) in data.iter().enumerate().take(10) {
+            if i == 9 && byte > 1 {
+                break;
+            }
+            value |= ((byte & 0x7f) as u64) << (i * 7);
+            if byte & 0x80 != 0 {
+                continue;
+            }
+            let len = usize::try_from(value).unwrap_or(usize::MAX);
+            if len > self.max_frame {
+                return Err(frame_too_large(len, self.max_frame));
+            }
+            let start = i + 1;
+            if data.len() - start < len {
+                return Ok(None);
+            }
+            return Ok(Some(Frame { payload: start..start + len, consumed: start + len }));
+        }
+        if data.len() < 10 {
+            return Ok(None);
+        }
+        Err(io::Error::new(io::ErrorKind::InvalidData, "varint length prefix overflows u64"))
+    }
+}
+
+impl Encoder for VarintDelimited {
+    fn encode<W: Write>(&mut self, payload: &[u8], dst: &mut W) -> io::Result<()> {
+        if payload.len() > self.max_frame {
+            return Err(frame_too_large(payload.len(), self.max_frame));
+        }
+        let mut prefix = [0u8; 10];
+        let mut value = payload.len() as u64;
+        let mut n = 0;
+        loop {
+            prefix[n] = (value & 0x7f) as u8;
+            value >>= 7;
+            n += 1;
+            if value == 0 {
+                break;
+            }
+            prefix[n - 1] |= 0x80;
+        }
+        dst.write_all(&prefix[..n])?;
+        dst.write_all(payload)?;
+        Ok(())
+    }
+}
+
+/// 以分隔符结尾的帧，帧内容不包括分隔符
+#[derive(Debug, Clone)]
+pub struct Delimited {
+    delimiter: Vec<u8>,
+    max_frame: usize,
+    /// 上次查找时已确认不含分隔符的前缀长度
+    searched: usize,
+}
+
+impl Delimited {
+    /// 创建以delimiter分隔的编解码器
+    ///
+    /// # Panics
+    ///
+    /// delimiter为空时panic
+    pub fn new(delimiter: &[u8]) -> Self {
+        assert!(!delimiter.is_empty(), "empty delimiter");
+        Self {
+            delimiter: delimiter.to_vec(),
+            max_frame: DEFAULT_MAX_FRAME,
+            searched: 0,
+        }
+    }
+
+    /// 设置最大帧长度，不包括分隔符
+    pub fn with_max_frame(mut self, max_frame: usize) -> Self {
+        self.max_frame = max_frame;
+        self
+    }
+}
+
+impl Decoder for Delimited {
+    fn find_frame(&mut self, data: &[u8]) -> io::Result<Option<Frame>> {
+        let delim = &self.delimiter[..];
+        let start = self.searched.min(data.len());
+        match data[start..].windows(delim.len()).position(|window| window == delim) {
+            Some(pos) => {
+                let end = start + pos;
+                self.searched = 0;
+                if end > self.max_frame {
+                    return Err(frame_too_large(end, self.max_frame));
+                }
+                Ok(Some(Frame { payload: 0..end, consumed: end + delim.len() }))
+            }
+            None => {
+                // 分隔符可能跨越本次数据的结尾，保留最后delim.len() - 1个字节下次重新检查
+                self.searched = data.len().saturating_sub(delim.len() - 1);
+                if self.searched > self.max_frame {
+                    return Err(frame_too_large(self.searched, self.max_frame));
+                }
+                Ok(None)
+            }
+        }
+    }
+}
+
+impl Encoder for Delimited {
+    fn encode<W: Write>(&mut self, payload: &[u8], dst: &mut W) -> io::Result<()> {
+        if payload.len() > self.max_frame {
+            return Err(frame_too_large(payload.len(), self.max_frame));
+        }
+        dst.write_all(payload)?;
+        dst.write_all(&self.delimiter)?;
+        Ok(())
+    }
+}
+
+/// 按行分帧，行以LF或CRLF结尾，帧内容不包括行尾；编码时使用LF
+#[derive(Debug, Clone)]
+pub struct Lines {
+    inner: Delimited,
+}
+
+impl Lines {
+    /// 创建使用默认最大行长度的编解码器
+    pub fn new() -> Self {
+        Self { inner: Delimited::new(b"\n") }
+    }
+
+    /// 设置最大行长度，不包括行尾
+    pub fn with_max_frame(mut self, max_frame: usize) -> Self {
+        // 为行尾可能的CR多留一个字节，超出部分在find_frame中检查
+        self.inner = self.inner.with_max_frame(max_frame.saturating_add(1));
+        self
+    }
+}
+
+impl Default for Lines {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Decoder for Lines {
+    fn find_frame(&mut self, data: &[u8]) -> io::Result<Option<Frame>> {
+        let Some(mut frame) = self.inner.find_frame(data)? else {
+            return Ok(None);
+        };
+        if data[frame.payload.clone()].ends_with(b"\r") {
+            frame.payload.end -= 1;
+        }
+        let max = self.inner.max_frame - 1;
+        if frame.payload.len() > max {
+            return Err(frame_too_large(frame.payload.len(), max));
+        }
+        Ok(Some(frame))
+    }
+}
+
+impl Encoder for Lines {
+    fn encode<W: Write>(&mut self, payload: &[u8], dst: &mut W) -> io::Result<()> {
+        self.inner.encode(payload, dst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 逐字节喂入数据，收集解出的帧
+    fn feed<D: Decoder>(decoder: &mut D, input: &[u8]) -> io::Result<Vec<Vec<u8>>> {
+        let mut buf = MBuf::with_capacity(4);
+        let mut frames = Vec::new();
+        for &byte in input {
+            buf.append(&[byte]);
+            while let Some(frame) = decoder.decode(&mut buf)? {
+                frames.push(frame.to_vec());
+            }
+        }
+        assert!(buf.is_empty());
+        Ok(frames)
+    }
+
+    #[test]
+    fn test_length_delimited() {
+        for width in [1, 2, 4, 8] {
+            for little in [false, true] {
+                let mut codec = LengthDelimited::new(width);
+                if little {
+                    codec = codec.little_endian();
+                }
+                let mut wire = MBuf::with_capacity(0);
+                codec.encode(b"hello", &mut wire).unwrap();
+                codec.encode(b"", &mut wire).unwrap();
+                codec.encode(b"world!", &mut wire).unwrap();
+                assert_eq!(wire.len(), 3 * width + 11);
+                let frames = feed(&mut codec, &wire).unwrap();
+                assert_eq!(frames, [&b"hello"[..], b"", b"world!"]);
+            }
+        }
+
+        // 前缀包含自身长度
+        let mut codec = LengthDelimited::new(2).with_adjustment(-2);
+        assert_eq!(feed(&mut codec, b"\x00\x05abc").unwrap(), [b"abc"]);
+        let mut wire = MBuf::with_capacity(0);
+        codec.encode(b"abc", &mut wire).unwrap();
+        assert_eq!(&wire[..], b"\x00\x05abc");
+        assert!(feed(&mut codec, b"\x00\x01").is_err());
+
+        let mut codec = LengthDelimited::new(4).with_max_frame(4);
+        assert!(feed(&mut codec, b"\x00\x00\x00\x05").is_err());
+        assert!(LengthDelimited::new(1).encode(&[0; 256], &mut wire).is_err());
+    }
+
+    #[test]
+    fn test_decode_shares_memory() {
+        let mut buf = MBuf::with_capacity(32);
+        buf.append(b"\x03abc\x02de\x01");
+        let base = buf.as_ptr();
+        let mut codec = LengthDelimited::new(1);
+        let first = codec.decode(&mut buf).unwrap().unwrap();
+        let second = codec.decode(&mut buf).unwrap().unwrap();
+        assert_eq!(first.as_ptr(), base.wrapping_add(1));
+        assert_eq!((&first[..], &second[..]), (&b"abc"[..], &b"de"[..]));
+        assert!(codec.decode(&mut buf).unwrap().is_none());
+        assert_eq!(&buf[..], b"\x01");
+    }
+
+    #[test]
+    fn test_varint() {
+        let mut codec = VarintDelimited::new();
+        let long = vec![7u8; 300];
+        let mut wire = MBuf::with_capacity(0);
+        codec.encode(b"x", &mut wire).unwrap();
+        codec.encode(&long, &mut wire).unwrap();
+        assert_eq!(&wire[2..4], &[0xac, 0x02]);
+        let frames = feed(&mut codec, &wire).unwrap();
+        assert_eq!(frames, [b"x".to_vec(), long]);
+
+        assert!(feed(&mut VarintDelimited::new().with_max_frame(10), &[0x0b]).is_err());
+        assert!(feed(&mut codec, &[0xff; 11]).is_err());
+    }
+
+    #[test]
+    fn test_lines() {
+        let mut codec = Lines::new();
+        let frames = feed(&mut codec, b"GET / HTTP/1.1\r\nHost: x\n\r\n\n").unwrap();
+        assert_eq!(frames, [&b"GET / HTTP/1.1"[..], b"Host: x", b"", b""]);
+
+        let mut codec = Lines::new().with_max_frame(3);
+        assert_eq!(feed(&mut codec, b"abc\r\n").unwrap(), [b"abc"]);
+        assert!(feed(&mut codec, b"abcd\n").is_err());
+        assert!(feed(&mut codec, b"abcde").is_err());
+    }
+
+    #[test]
+    fn test_delimited() {
+        let mut codec = Delimited::new(b"\r\n\r\n");
+        let frames = feed(&mut codec, b"a\r\nb\r\n\r\n\r\n\r\nc\r\n\r\n").unwrap();
+        assert_eq!(frames, [&b"a\r\nb"[..], b"", b"c"]);
+
+        let mut wire = MBuf::with_capacity(0);
+        codec.encode(b"end", &mut wire).unwrap();
+        assert_eq!(&wire[..], b"end\r\n\r\n");
+
+        let mut codec = Delimited::new(b"--").with_max_frame(2);
+        assert!(feed(&mut codec, b"abcd").is_err());
+    }
+}
@@ -0,0 +1,107 @@
+// MIT License
+//
+// Copyright (c) 2023 gaosg
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! futures-io的`AsyncRead`/`AsyncWrite`实现及异步读写辅助函数
+//!
+//! 缓冲区都在内存中，读写总是立即完成
+
+use std::io::{self, IoSlice, Read, Write};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use futures_io::{AsyncRead, AsyncWrite};
+use crate::async_util::{impl_async_write, read_into_with, write_chain_with};
+use super::{ChainCursor, Cursor, MBuf, MChain};
+
+impl AsyncRead for Cursor<'_> {
+    fn poll_read(self: Pin<&mut Self>, _cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        Poll::Ready(self.get_mut().read(buf))
+    }
+}
+
+impl AsyncRead for ChainCursor<'_> {
+    fn poll_read(self: Pin<&mut Self>, _cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        Poll::Ready(self.get_mut().read(buf))
+    }
+}
+
+/// 读取后丢弃已读的数据
+impl AsyncRead for MBuf {
+    fn poll_read(self: Pin<&mut Self>, _cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        Poll::Ready(self.get_mut().read(buf))
+    }
+}
+
+impl_async_write!(AsyncWrite, poll_close, [MBuf, MChain], {});
+
+/// 从reader读取数据直接写入buf的空闲空间，没有空闲空间时先扩容，返回读取的字节数
+pub async fn read_into<R: AsyncRead + Unpin + ?Sized>(buf: &mut MBuf, reader: &mut R) -> io::Result<usize> {
+    read_into_with(buf, |cx, spare| Pin::new(&mut *reader).poll_read(cx, spare)).await
+}
+
+/// 用向量写将链中的数据全部写入writer，已写出的数据从链中移除，返回写出的字节数
+pub async fn write_chain<W: AsyncWrite + Unpin + ?Sized>(chain: &mut MChain, writer: &mut W) -> io::Result<usize> {
+    write_chain_with(chain, |cx, iov| Pin::new(&mut *writer).poll_write_vectored(cx, iov)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::IoSlice;
+    use futures_util::io::{AsyncReadExt, AsyncWriteExt};
+    use super::{read_into, write_chain};
+    use crate::{Cursor, MBuf, MChain};
+
+    #[tokio::test]
+    async fn test_async_read_write() {
+        let mut buf = MBuf::with_capacity(4);
+        buf.write_all(b"futures ").await.unwrap();
+        assert_eq!(buf.write_vectored(&[IoSlice::new(b"i"), IoSlice::new(b"o")]).await.unwrap(), 2);
+        assert_eq!(&buf[..], b"futures io");
+
+        let mut out = Vec::new();
+        Cursor::new(&buf).read_to_end(&mut out).await.unwrap();
+        assert_eq!(out, b"futures io");
+
+        let chain: MChain = [buf.slice(..3), buf.slice(3..)].into_iter().collect();
+        let mut out = [0u8; 10];
+        chain.cursor().read_exact(&mut out).await.unwrap();
+        assert_eq!(&out, b"futures io");
+
+        let mut head = [0u8; 8];
+        buf.read_exact(&mut head).await.unwrap();
+        assert_eq!(&buf[..], b"io");
+    }
+
+    #[tokio::test]
+    async fn test_read_into_write_chain() {
+        let mut reader = &b"0123456789"[..];
+        let mut buf = MBuf::with_capacity(4);
+        assert_eq!(read_into(&mut buf, &mut reader).await.unwrap(), 4);
+        while read_into(&mut buf, &mut reader).await.unwrap() > 0 {}
+        assert_eq!(&buf[..], b"0123456789");
+
+        let mut chain: MChain = [buf.slice(..2), buf.slice(2..)].into_iter().collect();
+        let mut out = Vec::new();
+        assert_eq!(write_chain(&mut chain, &mut out).await.unwrap(), 10);
+        assert!(chain.is_empty());
+        assert_eq!(out, b"0123456789");
+    }
+}
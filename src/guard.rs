@@ -0,0 +1,190 @@
+// MIT License
+//
+// Copyright (c) 2023 gaosg
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! 调试用的内存保护：分配前后的canary、归还池中时的填充，以及可选的保护页
+//!
+//! 不带`guard-pages`时内存布局为`[canary][data][canary]`；
+//! 带`guard-pages`时为`[canary][data][不可访问页]`，越过末尾的访问立即触发段错误
+
+use std::ptr;
+
+/// canary长度
+const CANARY_LEN: usize = 16;
+/// canary填充值
+const CANARY: [u8; CANARY_LEN] = *b"\xde\xad\xbe\xef\xde\xad\xbe\xef\xde\xad\xbe\xef\xde\xad\xbe\xef";
+/// 归还池中的内存填充值
+pub(crate) const POISON: u8 = 0xa5;
+
+/// 分配capacity字节，数据前后放置保护区
+pub(crate) fn alloc(capacity: usize) -> *mut u8 {
+    let data = unsafe { raw::alloc(capacity) };
+    unsafe {
+        ptr::copy_nonoverlapping(CANARY.as_ptr(), data.sub(CANARY_LEN), CANARY_LEN);
+        #[cfg(not(feature = "guard-pages"))]
+        ptr::copy_nonoverlapping(CANARY.as_ptr(), data.add(capacity), CANARY_LEN);
+    }
+    data
+}
+
+/// 校验canary后释放alloc分配的内存
+///
+/// # Safety
+///
+/// data和capacity必须来自同一次alloc
+pub(crate) unsafe fn dealloc(data: *mut u8, capacity: usize) {
+    // 已在panic中时不再校验，避免二次panic导致进程中止
+    if !std::thread::panicking() {
+        check(data, capacity);
+    }
+    raw::dealloc(data, capacity);
+}
+
+/// 校验数据前后的canary，被改写时panic
+///
+/// # Safety
+///
+/// data和capacity必须来自同一次alloc且尚未释放
+pub(crate) unsafe fn check(data: *const u8, capacity: usize) {
+    let before = std::slice::from_raw_parts(data.sub(CANARY_LEN), CANARY_LEN);
+    assert!(before == CANARY, "MBuf buffer underrun detected at {:p}", data);
+    #[cfg(not(feature = "guard-pages"))]
+    {
+        let after = std::slice::from_raw_parts(data.add(capacity), CANARY_LEN);
+        assert!(after == CANARY, "MBuf buffer overrun detected at {:p}+{}", data, capacity);
+    }
+    #[cfg(feature = "guard-pages")]
+    let _ = capacity;
+}
+
+/// 用POISON填充内存，使读取已归还缓冲区的代码容易暴露
+///
+/// # Safety
+///
+/// data必须指向至少capacity字节可写的内存
+pub(crate) unsafe fn poison(data: *mut u8, capacity: usize) {
+    ptr::write_bytes(data, POISON, capacity);
+}
+
+#[cfg(not(feature = "guard-pages"))]
+mod raw {
+    use std::alloc::{alloc_zeroed, dealloc as free, handle_alloc_error, Layout};
+    use super::CANARY_LEN;
+
+    fn layout(capacity: usize) -> Layout {
+        Layout::from_size_align(capacity + 2 * CANARY_LEN, 1).unwrap()
+    }
+
+    pub(super) unsafe fn alloc(capacity: usize) -> *mut u8 {
+        let layout = layout(capacity);
+        let base = alloc_zeroed(layout);
+        if base.is_null() {
+            handle_alloc_error(layout);
+        }
+        base.add(CANARY_LEN)
+    }
+
+    pub(super) unsafe fn dealloc(data: *mut u8, capacity: usize) {
+        free(data.sub(CANARY_LEN), layout(capacity));
+    }
+}
+
+#[cfg(feature = "guard-pages")]
+mod raw {
+    use super::CANARY_LEN;
+
+    fn page_size() -> usize {
+        unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize }
+    }
+
+    /// 可访问部分的长度，数据紧贴其末尾
+    fn accessible_len(capacity: usize) -> usize {
+        (capacity + CANARY_LEN).next_multiple_of(page_size())
+    }
+
+    pub(super) unsafe fn alloc(capacity: usize) -> *mut u8 {
+        let accessible = accessible_len(capacity);
+        let base = libc::mmap(
+            std::ptr::null_mut(),
+            accessible + page_size(),
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+            -1,
+            0,
+        );
+        assert!(base != libc::MAP_FAILED, "mmap failed: {}", std::io::Error::last_os_error());
+        let base = base as *mut u8;
+        let rc = libc::mprotect(base.add(accessible).cast(), page_size(), libc::PROT_NONE);
+        assert!(rc == 0, "mprotect failed: {}", std::io::Error::last_os_error());
+        base.add(accessible - capacity)
+    }
+
+    pub(super) unsafe fn dealloc(data: *mut u8, capacity: usize) {
+        let accessible = accessible_len(capacity);
+        let base = data.sub(accessible - capacity);
+        libc::munmap(base.cast(), accessible + page_size());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{MBuf, MPool};
+
+    #[test]
+    fn test_poison_on_free() {
+        let pool = MPool::new(1, 32);
+        let mut buf = pool.alloc();
+        buf.append(b"stale");
+        pool.free(buf);
+
+        let mut buf = pool.alloc();
+        assert!(buf.spare_capacity_mut().iter().all(|&b| b == super::POISON));
+        pool.free(buf);
+    }
+
+    #[test]
+    fn test_full_capacity_is_usable() {
+        for capacity in [1, 15, 4096, 5000] {
+            let mut buf = MBuf::with_capacity(capacity);
+            buf.append(&vec![0x5a; capacity]);
+            buf.resize(capacity * 2);
+            assert_eq!(buf.len(), capacity);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "underrun")]
+    fn test_underrun_detected() {
+        let mut buf = MBuf::with_capacity(8);
+        unsafe { *buf.spare_capacity_mut().as_mut_ptr().sub(1) = 0 };
+        drop(buf);
+    }
+
+    #[cfg(not(feature = "guard-pages"))]
+    #[test]
+    #[should_panic(expected = "overrun")]
+    fn test_overrun_detected() {
+        let pool = MPool::new(0, 8);
+        let mut buf = pool.alloc();
+        unsafe { *buf.spare_capacity_mut().as_mut_ptr().add(8) = 0 };
+        pool.free(buf);
+    }
+}
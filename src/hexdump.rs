@@ -0,0 +1,219 @@
+// MIT License
+//
+// Copyright (c) 2023 gaosg
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::fmt;
+use super::{MBuf, MChain};
+
+/// 默认情况下`{:?}`最多显示的字节数
+pub const DEFAULT_MAX_BYTES: usize = 32;
+
+/// 十六进制格式化器
+///
+/// `{:?}`输出空格分隔的十六进制字节，超过上限时截断；
+/// `{:#?}`输出与`hexdump -C`相同的完整格式，包括偏移、ASCII列和重复行折叠
+#[derive(Clone)]
+pub struct Hexdump<'a> {
+    parts: Vec<&'a [u8]>,
+    len: usize,
+    max_bytes: usize,
+}
+
+impl<'a> Hexdump<'a> {
+    /// 格式化连续的数据
+    pub fn new(data: &'a [u8]) -> Self {
+        Self::from_parts(vec![data])
+    }
+
+    /// 格式化链中的全部分段，不复制数据
+    pub fn chain(chain: &'a MChain) -> Self {
+        Self::from_parts(chain.segments().map(|seg| &seg[..]).collect())
+    }
+
+    /// 设置`{:?}`最多显示的字节数
+    pub fn with_max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    fn from_parts(parts: Vec<&'a [u8]>) -> Self {
+        let len = parts.iter().map(|part| part.len()).sum();
+        Self { parts, len, max_bytes: DEFAULT_MAX_BYTES }
+    }
+
+    fn bytes(&self) -> impl Iterator<Item = u8> + '_ {
+        self.parts.iter().flat_map(|part| part.iter().copied())
+    }
+
+    fn fmt_short(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, byte) in self.bytes().take(self.max_bytes).enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "{:02x}", byte)?;
+        }
+        if self.len > self.max_bytes {
+            write!(f, " ... ({} bytes)", self.len)?;
+        }
+        Ok(())
+    }
+
+    fn fmt_canonical(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut bytes = self.bytes();
+        let mut prev = None;
+        let mut squeezing = false;
+        let mut offset = 0;
+        loop {
+            let mut line = [0u8; 16];
+            let mut n = 0;
+            while n < 16 {
+                match bytes.next() {
+                    Some(byte) => line[n] = byte,
+                    None => break,
+                }
+                n += 1;
+            }
+            if n == 0 {
+                break;
+            }
+
+            // 与上一行完全相同的整行折叠为一个*
+            if n == 16 && prev == Some(line) {
+                if !squeezing {
+                    writeln!(f, "*")?;
+                    squeezing = true;
+                }
+                offset += n;
+                continue;
+            }
+            squeezing = false;
+            prev = Some(line);
+
+            write!(f, "{:08x} ", offset)?;
+            for (i, byte) in line.iter().enumerate() {
+                if i == 8 {
+                    f.write_str(" ")?;
+                }
+                if i < n {
+                    write!(f, " {:02x}", byte)?;
+                } else {
+                    f.write_str("   ")?;
+                }
+            }
+            f.write_str("  |")?;
+            for &byte in &line[..n] {
+                let c = if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '.' };
+                write!(f, "{}", c)?;
+            }
+            writeln!(f, "|")?;
+            offset += n;
+        }
+        if offset > 0 {
+            writeln!(f, "{:08x}", offset)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Hexdump<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if f.alternate() {
+            self.fmt_canonical(f)
+        } else {
+            self.fmt_short(f)
+        }
+    }
+}
+
+impl MBuf {
+    /// 获取数据的十六进制格式化器
+    pub fn hexdump(&self) -> Hexdump<'_> {
+        Hexdump::new(self)
+    }
+}
+
+impl MChain {
+    /// 获取全部数据的十六进制格式化器
+    pub fn hexdump(&self) -> Hexdump<'_> {
+        Hexdump::chain(self)
+    }
+}
+
+impl fmt::Debug for MBuf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if f.alternate() {
+            writeln!(f, "MBuf[{}]", self.len())?;
+        } else {
+            write!(f, "MBuf[{}] ", self.len())?;
+        }
+        self.hexdump().fmt(f)
+    }
+}
+
+impl fmt::Debug for MChain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if f.alternate() {
+            writeln!(f, "MChain[{}; {} segments]", self.len(), self.segment_count())?;
+        } else {
+            write!(f, "MChain[{}; {} segments] ", self.len(), self.segment_count())?;
+        }
+        self.hexdump().fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_short() {
+        let mut buf = MBuf::with_capacity(16);
+        buf.append(b"hi\x00\xff");
+        assert_eq!(format!("{:?}", buf), "MBuf[4] 68 69 00 ff");
+        assert_eq!(format!("{:?}", Hexdump::new(b"abc").with_max_bytes(2)), "61 62 ... (3 bytes)");
+        assert_eq!(format!("{:?}", MBuf::with_capacity(0)), "MBuf[0] ");
+    }
+
+    #[test]
+    fn test_canonical() {
+        let mut data = b"hello world\n".to_vec();
+        data.extend_from_slice(&[0u8; 40]);
+        data.extend_from_slice(b"end");
+        let expected = "\
+00000000  68 65 6c 6c 6f 20 77 6f  72 6c 64 0a 00 00 00 00  |hello world.....|
+00000010  00 00 00 00 00 00 00 00  00 00 00 00 00 00 00 00  |................|
+*
+00000030  00 00 00 00 65 6e 64                              |....end|
+00000037
+";
+        assert_eq!(format!("{:#?}", Hexdump::new(&data)), expected);
+
+        // 链的输出与连续数据相同
+        let mut chain = MChain::new();
+        for part in data.chunks(5) {
+            chain.extend_from_slice(part);
+            chain.push_back(MBuf::with_capacity(0));
+        }
+        assert_eq!(format!("{:#?}", chain.hexdump()), expected);
+        assert!(format!("{:#?}", chain).starts_with("MChain[55; "));
+        assert_eq!(format!("{:#?}", Hexdump::new(b"")), "");
+    }
+}
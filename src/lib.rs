@@ -21,8 +21,46 @@
 // SOFTWARE.
 mod mbuf;
 mod mbuf_pool;
+mod mchain;
+mod frag;
+mod read_iter;
+mod hexdump;
+#[cfg(feature = "debug-guards")]
+mod guard;
+#[cfg(feature = "tracking")]
+pub mod tracking;
+#[cfg(all(feature = "guard-pages", not(unix)))]
+compile_error!("the `guard-pages` feature requires a unix target");
+pub mod checksum;
+pub mod proto;
+pub mod pcap;
+pub mod framing;
+#[cfg(target_os = "linux")]
+mod mmsg;
+#[cfg(feature = "tokio-codec")]
+mod tokio_codec;
+#[cfg(any(feature = "tokio", feature = "futures-io"))]
+mod async_util;
+#[cfg(feature = "tokio")]
+pub mod tokio_io;
+#[cfg(feature = "futures-io")]
+pub mod futures_io;
+#[cfg(feature = "serde")]
+pub mod binary;
+#[cfg(any(feature = "deflate", feature = "gzip", feature = "lz4", feature = "zstd"))]
+pub mod compress;
+#[cfg(feature = "aead")]
+pub mod aead;
 
 
-pub use  mbuf::MBuf;
+pub use  mbuf::{MBuf, Meta};
 pub use  mbuf::Cursor;
-pub use  mbuf_pool::MPool;
\ No newline at end of file
+pub use  mbuf_pool::{MPool, OversizePolicy, PoolStats, PooledMBuf};
+pub use  mchain::{ChainCursor, MChain, Segments, IOV_MAX};
+pub use  frag::{Reassembler, ReassemblyError};
+pub use  read_iter::PooledReadIter;
+pub use  hexdump::Hexdump;
+#[cfg(target_os = "linux")]
+pub use  mmsg::{recv_batch, send_batch, Datagram};
+#[cfg(feature = "tokio-codec")]
+pub use  tokio_codec::PooledCodec;
@@ -22,31 +22,249 @@
 
 #![allow(dead_code)]
 
+use std::any::{Any, TypeId};
 use std::borrow::{Borrow, BorrowMut};
+use std::hash::{Hash, Hasher};
 use std::ptr;
-use std::ops::{Deref, DerefMut};
-use std::sync::atomic::{AtomicUsize, Ordering};
-use std::io::{Read, Write, Result};
+use std::ops::{Bound, Deref, DerefMut, RangeBounds};
+use std::sync::{Arc, Weak};
+use std::sync::atomic::{compiler_fence, AtomicBool, AtomicUsize, Ordering};
+use std::io::{IoSlice, Read, Write, Result};
+use std::net::SocketAddr;
+use std::time::Duration;
+use crate::mbuf_pool::PoolInner;
+
+/// 私有区的构造函数
+pub(crate) type PrivateInit = fn() -> Box<dyn Any + Send + Sync>;
+
+/// 私有区的重置函数
+pub(crate) type PrivateReset = fn(&mut (dyn Any + Send + Sync));
+
+/// 附加在MBuf上的用户私有区，类似DPDK mempool的priv_size
+pub(crate) struct Private {
+    value: Box<dyn Any + Send + Sync>,
+    init: PrivateInit,
+    reset: PrivateReset,
+}
+
+impl Private {
+    /// 创建类型为P的私有区，初始值为P::default()
+    pub(crate) fn new<P: Default + Send + Sync + 'static>() -> Self {
+        Self {
+            value: Box::new(P::default()),
+            init: || Box::new(P::default()),
+            reset: |value| {
+                if let Some(value) = value.downcast_mut::<P>() {
+                    *value = P::default();
+                }
+            },
+        }
+    }
+
+    /// 原地重新初始化为默认值
+    pub(crate) fn reset(&mut self) {
+        (self.reset)(&mut *self.value);
+    }
+
+    /// 以相同类型创建一个新的默认私有区
+    fn fresh(&self) -> Self {
+        Self {
+            value: (self.init)(),
+            init: self.init,
+            reset: self.reset,
+        }
+    }
+}
+
+/// 分配指定大小的内存，容量为0时不分配
+///
+/// 内存清零，保证数据之后的空闲空间可以作为`&mut [u8]`交给读取方填充
+fn alloc_bytes(capacity: usize) -> *mut u8 {
+    if capacity == 0 {
+        return ptr::NonNull::dangling().as_ptr();
+    }
+    #[cfg(feature = "debug-guards")]
+    return crate::guard::alloc(capacity);
+    #[cfg(not(feature = "debug-guards"))]
+    alloc_plain(capacity)
+}
+
+#[cfg(not(feature = "debug-guards"))]
+fn alloc_plain(capacity: usize) -> *mut u8 {
+    let layout = std::alloc::Layout::from_size_align(capacity, 1).unwrap();
+    let data = unsafe { std::alloc::alloc_zeroed(layout) };
+    if data.is_null() {
+        std::alloc::handle_alloc_error(layout);
+    }
+    data
+}
+
+/// 释放由alloc_bytes分配的内存
+unsafe fn dealloc_bytes(data: *mut u8, capacity: usize) {
+    if capacity == 0 {
+        return;
+    }
+    #[cfg(feature = "debug-guards")]
+    crate::guard::dealloc(data, capacity);
+    #[cfg(not(feature = "debug-guards"))]
+    dealloc_plain(data, capacity);
+}
+
+#[cfg(not(feature = "debug-guards"))]
+unsafe fn dealloc_plain(data: *mut u8, capacity: usize) {
+    let layout = std::alloc::Layout::from_size_align(capacity, 1).unwrap();
+    std::alloc::dealloc(data, layout);
+}
+
+/// 按整页分配capacity字节，用于需要锁定的内存，munlock时不会解锁同一页上的其他分配
+///
+/// 不放置debug-guards的canary
+fn alloc_pages(capacity: usize) -> *mut u8 {
+    if capacity == 0 {
+        return ptr::NonNull::dangling().as_ptr();
+    }
+    let layout = page_layout(capacity);
+    let data = unsafe { std::alloc::alloc_zeroed(layout) };
+    if data.is_null() {
+        std::alloc::handle_alloc_error(layout);
+    }
+    data
+}
+
+/// 释放由alloc_pages分配的内存
+unsafe fn dealloc_pages(data: *mut u8, capacity: usize) {
+    if capacity > 0 {
+        std::alloc::dealloc(data, page_layout(capacity));
+    }
+}
+
+fn page_layout(capacity: usize) -> std::alloc::Layout {
+    let page = page_size();
+    std::alloc::Layout::from_size_align(capacity.next_multiple_of(page), page).unwrap()
+}
+
+#[cfg(unix)]
+fn page_size() -> usize {
+    unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize }
+}
+
+#[cfg(not(unix))]
+fn page_size() -> usize {
+    4096
+}
+
+/// 用不会被编译器优化掉的写入将内存清零
+fn zeroize(data: *mut u8, len: usize) {
+    for i in 0..len {
+        unsafe { ptr::write_volatile(data.add(i), 0) };
+    }
+    compiler_fence(Ordering::SeqCst);
+}
+
+/// 锁定内存页，防止被换出到磁盘
+#[cfg(unix)]
+fn lock_pages(data: *mut u8, len: usize) -> std::io::Result<()> {
+    if len == 0 || unsafe { libc::mlock(data as *const libc::c_void, len) } == 0 {
+        return Ok(());
+    }
+    Err(std::io::Error::last_os_error())
+}
+
+#[cfg(not(unix))]
+fn lock_pages(_data: *mut u8, _len: usize) -> std::io::Result<()> {
+    Err(std::io::ErrorKind::Unsupported.into())
+}
+
+#[cfg(unix)]
+fn unlock_pages(data: *mut u8, len: usize) {
+    if len > 0 {
+        unsafe { libc::munlock(data as *const libc::c_void, len) };
+    }
+}
+
+#[cfg(not(unix))]
+fn unlock_pages(_data: *mut u8, _len: usize) {}
+
+/// 随缓冲区携带的报文元数据
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Meta {
+    /// 报文的源地址
+    pub src_addr: Option<SocketAddr>,
+    /// 报文的时间戳（自UNIX纪元起）
+    pub timestamp: Option<Duration>,
+    /// 报文超出缓冲区容量，只保存了前面部分
+    pub truncated: bool,
+}
+
+/// 将范围转换为[start, end)，超出len时panic
+pub(crate) fn bounds(range: impl RangeBounds<usize>, len: usize) -> (usize, usize) {
+    let start = match range.start_bound() {
+        Bound::Included(&n) => n,
+        Bound::Excluded(&n) => n + 1,
+        Bound::Unbounded => 0,
+    };
+    let end = match range.end_bound() {
+        Bound::Included(&n) => n + 1,
+        Bound::Excluded(&n) => n,
+        Bound::Unbounded => len,
+    };
+    assert!(start <= end && end <= len, "range {}..{} out of bounds for length {}", start, end, len);
+    (start, end)
+}
+
+/// 同一块内存的所有引用共享的状态
+struct Shared {
+    ref_count: AtomicUsize,
+    /// 释放内存和clear时清零
+    secure: AtomicBool,
+    /// 内存已通过mlock锁定
+    locked: AtomicBool,
+    /// 内存由alloc_pages按整页分配
+    paged: bool,
+}
+
+impl Shared {
+    fn new(secure: bool, locked: bool, paged: bool) -> *mut Shared {
+        Box::into_raw(Box::new(Shared {
+            ref_count: AtomicUsize::new(1),
+            secure: AtomicBool::new(secure),
+            locked: AtomicBool::new(locked),
+            paged,
+        }))
+    }
+}
 
 /// 类似Linux mbuf的高性能消息缓冲区
 pub struct MBuf {
     data: *mut u8,
+    offset: usize,
     len: usize,
     capacity: usize,
-    ref_count: *mut AtomicUsize,
+    shared: *mut Shared,
+    private: Option<Private>,
+    meta: Meta,
+    /// 分配出这块内存的池，最后一个引用释放时内存回到池中
+    origin: Option<Weak<PoolInner>>,
+    /// MPool分配时记录的编号
+    #[cfg(feature = "tracking")]
+    track_id: Option<u64>,
 }
 
 impl MBuf {
     /// 创建指定容量的新缓冲区
     pub fn with_capacity(capacity: usize) -> Self {
-        let layout = std::alloc::Layout::from_size_align(capacity, 1).unwrap();
-        let data = unsafe { std::alloc::alloc(layout) };
-        let ref_count = Box::into_raw(Box::new(AtomicUsize::new(1)));
+        let data = alloc_bytes(capacity);
         Self {
             data,
+            offset: 0,
             len: 0,
             capacity,
-            ref_count,
+            shared: Shared::new(false, false, false),
+            private: None,
+            meta: Meta::default(),
+            origin: None,
+            #[cfg(feature = "tracking")]
+            track_id: None,
         }
     }
     
@@ -54,96 +272,404 @@ impl MBuf {
     pub fn len(&self) -> usize {
         self.len
     }
+
+    /// 是否没有数据
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
     
     /// 获取缓冲区容量
     pub fn capacity(&self) -> usize {
         self.capacity
     }
+
+    /// 获取数据之前的空闲空间
+    pub fn headroom(&self) -> usize {
+        self.offset
+    }
+
+    /// 获取数据之后的空闲空间
+    pub fn tailroom(&self) -> usize {
+        self.capacity - self.offset - self.len
+    }
     
     /// 追加数据
     pub fn append(&mut self, data: &[u8]) -> usize {
-        let current_len = self.len;
-        let needed = current_len + data.len();
-        
-        if needed > self.capacity {
-            self.resize(needed);
-        }
+        self.reserve(data.len());
         
         let to_copy = data.len();
         unsafe {
             ptr::copy_nonoverlapping(
                 data.as_ptr(),
-                self.data.add(current_len),
+                self.data.add(self.offset + self.len),
                 to_copy
             );
         }
         
-        self.len = current_len + to_copy;
+        self.len += to_copy;
         to_copy
     }
 
     pub fn is_unique(&self) -> bool {
-        unsafe { (*self.ref_count).load(Ordering::Acquire) == 1 }
+        self.shared().ref_count.load(Ordering::Acquire) == 1
+    }
+
+    fn shared(&self) -> &Shared {
+        unsafe { &*self.shared }
     }
 
+    /// 确保数据之后至少有additional字节可写入，与其他引用共享内存时先复制一份
+    pub fn reserve(&mut self, additional: usize) {
+        let needed = self.offset + self.len + additional;
+
+        if !self.is_unique() {
+            self.reallocate(std::cmp::max(needed, self.capacity));
+            return;
+        }
+        if needed <= self.capacity {
+            return;
+        }
+        if self.len + additional <= self.capacity {
+            // 前部已读取的空间足够，把数据移到开头
+            unsafe {
+                ptr::copy(self.data.add(self.offset), self.data, self.len);
+            }
+            self.offset = 0;
+            return;
+        }
+        self.resize(needed);
+    }
+
+    /// 写时复制：与其他引用共享内存时复制出独占的一份
+    pub fn make_unique(&mut self) {
+        if !self.is_unique() {
+            self.reallocate(self.capacity);
+        }
+    }
 
     /// 调整缓冲区大小
     pub fn resize(&mut self, new_capacity: usize) {
-        let current_len = self.len;
         let new_capacity = std::cmp::max(new_capacity, self.capacity + self.capacity / 2); // 按1.5倍增长
-        
-        let new_layout = std::alloc::Layout::from_size_align(new_capacity, 1).unwrap();
-        let new_data = unsafe { std::alloc::alloc(new_layout) };
+        self.reallocate(new_capacity);
+    }
+
+    /// 将数据复制到新分配的内存，尽量保留数据前的空闲空间
+    fn reallocate(&mut self, new_capacity: usize) {
+        let new_offset = if self.offset + self.len <= new_capacity { self.offset } else { 0 };
+        self.reallocate_at(new_capacity, new_offset);
+    }
+
+    /// 将数据复制到新分配内存的new_offset处
+    fn reallocate_at(&mut self, new_capacity: usize, new_offset: usize) {
+        self.reallocate_into(new_capacity, new_offset, self.shared().paged);
+    }
+
+    /// 将数据复制到新分配内存的new_offset处，paged时按整页分配
+    fn reallocate_into(&mut self, new_capacity: usize, new_offset: usize, paged: bool) {
+        // 独占时旧内存直接释放，新内存接替它归属来源池；
+        // 共享时旧内存留给其他引用归还，新内存不再属于池
+        let origin = if self.is_unique() { self.origin.take() } else { None };
+        let new_data = if paged { alloc_pages(new_capacity) } else { alloc_bytes(new_capacity) };
+        let secure = self.is_secure();
+        // 尽力锁定新内存，失败时仍可正常使用
+        let locked = self.shared().locked.load(Ordering::Relaxed) && lock_pages(new_data, new_capacity).is_ok();
 
         unsafe {
             ptr::copy_nonoverlapping(
-                self.data,
-                new_data,
-                current_len
+                self.data.add(self.offset),
+                new_data.add(new_offset),
+                self.len
             );
-            
-            //如果只有一个引用，释放旧内存
-            if (*self.ref_count).fetch_sub(1, Ordering::AcqRel) == 1 {
-                let old_layout = std::alloc::Layout::from_size_align(self.capacity, 1).unwrap();
-                std::alloc::dealloc(self.data, old_layout);
-                //释放旧引用
-                drop(Box::from_raw(self.ref_count));
-            }
+            self.release();
         }
-        
+
+        #[cfg(feature = "tracking")]
+        if origin.is_none() {
+            self.track_id = None;
+        }
+        self.origin = origin;
         self.data = new_data;
+        self.offset = new_offset;
         self.capacity = new_capacity;
-        self.ref_count = Box::into_raw(Box::new(AtomicUsize::new(1)));
+        self.shared = Shared::new(secure, locked, paged);
+    }
+
+    /// 在空缓冲区开头预留n字节的空闲空间，供之后通过prepend写入协议头
+    ///
+    /// # Panics
+    ///
+    /// 缓冲区中已有数据时panic
+    pub fn reserve_headroom(&mut self, n: usize) {
+        assert!(self.is_empty(), "reserve_headroom on a non-empty buffer");
+        self.offset = 0;
+        if n > self.capacity || !self.is_unique() {
+            self.reallocate_at(std::cmp::max(n, self.capacity), 0);
+        }
+        self.offset = n;
+    }
+
+    /// 在数据之前写入data，优先使用headroom，空间不足时重新分配
+    pub fn prepend(&mut self, data: &[u8]) {
+        if data.len() > self.offset || !self.is_unique() {
+            let extra = data.len().saturating_sub(self.offset);
+            self.reallocate_at(self.capacity + extra, self.offset + extra);
+        }
+        self.offset -= data.len();
+        self.len += data.len();
+        self[..data.len()].copy_from_slice(data);
+    }
+
+    /// 丢弃开头的n个字节，不移动数据
+    ///
+    /// # Panics
+    ///
+    /// n大于当前数据长度时panic
+    pub fn advance(&mut self, n: usize) {
+        assert!(n <= self.len, "advance {} beyond length {}", n, self.len);
+        self.offset += n;
+        self.len -= n;
+    }
+
+    /// 获取数据之后的空闲空间，用于直接读入数据，之后通过`commit`计入数据长度
+    ///
+    /// 与其他引用共享内存时先复制出独占的一份
+    pub fn spare_capacity_mut(&mut self) -> &mut [u8] {
+        self.make_unique();
+        let start = self.offset + self.len;
+        unsafe { std::slice::from_raw_parts_mut(self.data.add(start), self.capacity - start) }
+    }
+
+    /// 将空闲空间中前n个已写入的字节计入数据长度
+    ///
+    /// # Panics
+    ///
+    /// n大于tailroom时panic
+    pub fn commit(&mut self, n: usize) {
+        assert!(n <= self.tailroom(), "commit {} beyond tailroom {}", n, self.tailroom());
+        self.len += n;
+    }
+
+    /// 判断next是否紧接在本缓冲区数据之后且位于同一块内存中
+    pub(crate) fn is_contiguous_with(&self, next: &MBuf) -> bool {
+        self.shared == next.shared && self.offset + self.len == next.offset
+    }
+
+    /// 返回指定范围数据的零拷贝切片，与原缓冲区共享内存和引用计数
+    ///
+    /// # Panics
+    ///
+    /// 范围超出当前数据时panic
+    pub fn slice(&self, range: impl RangeBounds<usize>) -> MBuf {
+        let (start, end) = bounds(range, self.len);
+        let mut slice = self.clone();
+        slice.offset += start;
+        slice.len = end - start;
+        slice
+    }
+
+    /// 拆出前n个字节作为共享同一块内存的新缓冲区，自身从第n个字节开始
+    ///
+    /// # Panics
+    ///
+    /// n大于当前数据长度时panic
+    pub fn split_to(&mut self, n: usize) -> MBuf {
+        let head = self.slice(..n);
+        self.advance(n);
+        head
+    }
+
+    /// 截断数据到指定长度，len不小于当前长度时不做任何操作
+    pub fn truncate(&mut self, len: usize) {
+        if len < self.len {
+            self.len = len;
+        }
     }
 
     pub fn clear(&mut self) {
+        if self.is_secure() {
+            self.wipe();
+        }
+        self.offset = 0;
+        self.len = 0;
+    }
+
+    /// 开启后，最后一个引用释放内存、`clear`以及`resize`换下旧内存时都会将内存清零
+    ///
+    /// 设置作用于共享同一块内存的所有MBuf，扩容或写时复制得到的新内存沿用该设置；
+    /// 开启后不能关闭，任何一个引用都无法取消其他引用依赖的清零
+    pub fn set_secure(&mut self) {
+        self.shared().secure.store(true, Ordering::Relaxed);
+    }
+
+    /// 是否处于secure模式
+    pub fn is_secure(&self) -> bool {
+        self.shared().secure.load(Ordering::Relaxed)
+    }
+
+    /// 通过mlock锁定内存，防止被换出到磁盘，同时开启secure模式
+    ///
+    /// 锁定以页为单位，内存不是按整页分配时先复制到新分配的整页内存中，与其他引用不再共享；
+    /// 之后扩容得到的新内存会尽力锁定；受RLIMIT_MEMLOCK限制时返回错误
+    pub fn lock_memory(&mut self) -> std::io::Result<()> {
+        // 先开启secure，复制到整页内存后换下的旧内存在释放时同样被清零
+        self.set_secure();
+        if !self.shared().locked.load(Ordering::Relaxed) {
+            if !self.shared().paged {
+                self.reallocate_into(self.capacity, self.offset, true);
+            }
+            lock_pages(self.data, self.capacity)?;
+            self.shared().locked.store(true, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+
+    /// 独占内存时将整块内存清零；与其他引用共享时留给最后一个引用释放时清零
+    pub(crate) fn wipe(&mut self) {
+        if self.is_unique() {
+            zeroize(self.data, self.capacity);
+        }
+    }
+
+    /// 校验内存前后的canary，独占且非secure时填充POISON，用于归还池中的缓冲区
+    ///
+    /// secure缓冲区保持清零，不被填充覆盖
+    #[cfg(feature = "debug-guards")]
+    pub(crate) fn guard_on_free(&mut self) {
+        if self.capacity == 0 || self.shared().paged {
+            return;
+        }
+        unsafe { crate::guard::check(self.data, self.capacity) };
+        if self.is_unique() && !self.is_secure() {
+            unsafe { crate::guard::poison(self.data, self.capacity) };
+        }
+    }
+
+    /// 释放对当前内存的引用，最后一个引用负责把内存交还来源池，或清零、解锁和释放
+    unsafe fn release(&mut self) {
+        if (*self.shared).ref_count.fetch_sub(1, Ordering::AcqRel) == 1 {
+            if let Some(pool) = self.origin.take().and_then(|origin| origin.upgrade()) {
+                (*self.shared).ref_count.store(1, Ordering::Relaxed);
+                pool.reclaim(MBuf {
+                    data: self.data,
+                    offset: 0,
+                    len: 0,
+                    capacity: self.capacity,
+                    shared: self.shared,
+                    private: self.private.take(),
+                    meta: Meta::default(),
+                    origin: None,
+                    #[cfg(feature = "tracking")]
+                    track_id: self.track_id.take(),
+                });
+                return;
+            }
+            let shared = Box::from_raw(self.shared);
+            if shared.secure.load(Ordering::Relaxed) {
+                zeroize(self.data, self.capacity);
+            }
+            if shared.locked.load(Ordering::Relaxed) {
+                unlock_pages(self.data, self.capacity);
+            }
+            if shared.paged {
+                dealloc_pages(self.data, self.capacity);
+            } else {
+                dealloc_bytes(self.data, self.capacity);
+            }
+        }
+    }
+
+    /// 获取报文元数据
+    pub fn meta(&self) -> &Meta {
+        &self.meta
+    }
+
+    /// 获取可变的报文元数据
+    pub fn meta_mut(&mut self) -> &mut Meta {
+        &mut self.meta
+    }
+
+    /// 获取私有区，类型不匹配或未配置私有区时返回None
+    pub fn private<P: 'static>(&self) -> Option<&P> {
+        self.private.as_ref()?.value.downcast_ref::<P>()
+    }
+
+    /// 获取可变私有区，类型不匹配或未配置私有区时返回None
+    pub fn private_mut<P: 'static>(&mut self) -> Option<&mut P> {
+        self.private.as_mut()?.value.downcast_mut::<P>()
+    }
+
+    /// 私有区中值的类型
+    pub(crate) fn private_type_id(&self) -> Option<TypeId> {
+        self.private.as_ref().map(|private| (*private.value).type_id())
+    }
+
+    /// 设置私有区
+    pub(crate) fn set_private(&mut self, private: Option<Private>) {
+        self.private = private;
+    }
+
+    /// 将私有区重新初始化为默认值
+    pub(crate) fn reset_private(&mut self) {
+        if let Some(private) = self.private.as_mut() {
+            private.reset();
+        }
+    }
+
+    pub(crate) fn set_origin(&mut self, origin: Weak<PoolInner>) {
+        self.origin = Some(origin);
+    }
+
+    /// 是否由pool分配，不属于任何池时返回None
+    pub(crate) fn is_pooled_by(&self, pool: &Arc<PoolInner>) -> Option<bool> {
+        self.origin.as_ref().map(|origin| ptr::eq(origin.as_ptr(), Arc::as_ptr(pool)))
+    }
+
+    /// 重置长度、偏移和元数据，用于回到池中的缓冲区
+    pub(crate) fn reset(&mut self) {
+        self.offset = 0;
         self.len = 0;
+        self.meta = Meta::default();
+    }
+
+    /// 丢弃数据并重新分配为capacity字节的独占内存
+    pub(crate) fn shrink_to(&mut self, capacity: usize) {
+        self.reset();
+        self.reallocate_at(capacity, 0);
+    }
+
+    #[cfg(feature = "tracking")]
+    pub(crate) fn set_track_id(&mut self, id: u64) {
+        self.track_id = Some(id);
+    }
+
+    #[cfg(feature = "tracking")]
+    pub(crate) fn take_track_id(&mut self) -> Option<u64> {
+        self.track_id.take()
     }
 }
 
 impl Clone for MBuf {
     fn clone(&self) -> Self {
-        unsafe {
-            (*self.ref_count).fetch_add(1, Ordering::Relaxed);
-        }
+        self.shared().ref_count.fetch_add(1, Ordering::Relaxed);
         Self {
             data: self.data,
+            offset: self.offset,
             len: self.len,
             capacity: self.capacity,
-            ref_count: self.ref_count,
+            shared: self.shared,
+            // 私有区属于每个MBuf自身，克隆得到新的默认值
+            private: self.private.as_ref().map(Private::fresh),
+            meta: self.meta,
+            origin: self.origin.clone(),
+            #[cfg(feature = "tracking")]
+            track_id: self.track_id,
         }
     }
 }
 
 impl Drop for MBuf {
     fn drop(&mut self) {
-        unsafe {
-            if (*self.ref_count).fetch_sub(1, Ordering::AcqRel) == 1 {
-                let layout = std::alloc::Layout::from_size_align(self.capacity, 1).unwrap();
-                std::alloc::dealloc(self.data, layout);
-                drop(Box::from_raw(self.ref_count));
-            }
-        }  
+        unsafe { self.release() }
     }
 }
 
@@ -151,20 +677,20 @@ impl Deref for MBuf {
     type Target = [u8];
     
     fn deref(&self) -> &[u8] {
-        unsafe { std::slice::from_raw_parts(self.data, self.len) }
+        unsafe { std::slice::from_raw_parts(self.data.add(self.offset), self.len) }
     }
 }
 
 impl DerefMut for MBuf {
     fn deref_mut(&mut self) -> &mut [u8] {
         let len = self.len;
-        unsafe { std::slice::from_raw_parts_mut(self.data, len) }
+        unsafe { std::slice::from_raw_parts_mut(self.data.add(self.offset), len) }
     }
 }
 
 impl AsRef<[u8]> for MBuf  {
     fn as_ref(&self) -> &[u8] {
-        &*self
+        self
     }
 }
 
@@ -176,7 +702,7 @@ impl AsMut<[u8]> for MBuf {
 
 impl Borrow<[u8]> for MBuf {
     fn borrow(&self) -> &[u8] {
-        &*self
+        self
     }
 }
 
@@ -190,6 +716,25 @@ impl BorrowMut<[u8]> for MBuf {
 unsafe impl Send for MBuf {}
 unsafe impl Sync for MBuf {}
 
+/// 为游标生成按字节序读取数值的方法，游标需提供`read_array`
+macro_rules! impl_typed_reads {
+    ($($be:ident, $le:ident => $ty:ty;)*) => {
+        $(
+            #[doc = concat!("以大端序读取", stringify!($ty), "，剩余数据不足返回None")]
+            pub fn $be(&mut self) -> Option<$ty> {
+                self.read_array().map(<$ty>::from_be_bytes)
+            }
+
+            #[doc = concat!("以小端序读取", stringify!($ty), "，剩余数据不足返回None")]
+            pub fn $le(&mut self) -> Option<$ty> {
+                self.read_array().map(<$ty>::from_le_bytes)
+            }
+        )*
+    };
+}
+
+pub(crate) use impl_typed_reads;
+
 /// 用于遍历MBuf数据的游标结构
 pub struct Cursor<'a> {
     buf: &'a MBuf,
@@ -208,12 +753,13 @@ impl<'a> Cursor<'a> {
     }
     
     /// 获取下一个字节，如果到达末尾返回None
+    #[allow(clippy::should_implement_trait)]
     pub fn next(&mut self) -> Option<u8> {
         if self.pos >= self.buf.len() {
             return None;
         }
         
-        let byte = unsafe { *self.buf.data.add(self.pos) };
+        let byte = self.buf[self.pos];
         self.pos += 1;
         Some(byte)
     }
@@ -224,36 +770,152 @@ impl<'a> Cursor<'a> {
             return None;
         }
         
-        let slice = unsafe { std::slice::from_raw_parts(self.buf.data.add(self.pos), size) };
+        let buf: &'a MBuf = self.buf;
+        let slice = &buf[self.pos..self.pos + size];
         self.pos += size;
         Some(slice)
     }
     
+    /// 获取剩余未读取的字节数
+    pub fn remaining(&self) -> usize {
+        self.buf.len() - self.pos
+    }
+
+    /// 获取剩余未读取的数据，不移动游标
+    pub fn remaining_slice(&self) -> &'a [u8] {
+        let buf: &'a MBuf = self.buf;
+        &buf[self.pos..]
+    }
+
+    /// 读取N个字节到定长数组，如果剩余数据不足返回None
+    pub fn read_array<const N: usize>(&mut self) -> Option<[u8; N]> {
+        let slice = self.next_slice(N)?;
+        let mut out = [0u8; N];
+        out.copy_from_slice(slice);
+        Some(out)
+    }
+
+    impl_typed_reads! {
+        read_u16_be, read_u16_le => u16;
+        read_u32_be, read_u32_le => u32;
+        read_u64_be, read_u64_le => u64;
+        read_i16_be, read_i16_le => i16;
+        read_i32_be, read_i32_le => i32;
+        read_i64_be, read_i64_le => i64;
+        read_f32_be, read_f32_le => f32;
+        read_f64_be, read_f64_le => f64;
+    }
+    
     /// 重置游标位置
     pub fn reset(&mut self) {
         self.pos = 0;
     }
 }
 
+impl PartialEq for MBuf {
+    fn eq(&self, other: &MBuf) -> bool {
+        self[..] == other[..]
+    }
+}
+
+impl Eq for MBuf {}
+
+impl PartialEq<[u8]> for MBuf {
+    fn eq(&self, other: &[u8]) -> bool {
+        self[..] == *other
+    }
+}
+
+impl PartialEq<&[u8]> for MBuf {
+    fn eq(&self, other: &&[u8]) -> bool {
+        self[..] == **other
+    }
+}
+
+impl<const N: usize> PartialEq<[u8; N]> for MBuf {
+    fn eq(&self, other: &[u8; N]) -> bool {
+        self[..] == other[..]
+    }
+}
+
+impl<const N: usize> PartialEq<&[u8; N]> for MBuf {
+    fn eq(&self, other: &&[u8; N]) -> bool {
+        self[..] == other[..]
+    }
+}
+
+impl Hash for MBuf {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self[..].hash(state);
+    }
+}
+
+impl PartialOrd for MBuf {
+    fn partial_cmp(&self, other: &MBuf) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for MBuf {
+    fn cmp(&self, other: &MBuf) -> std::cmp::Ordering {
+        self[..].cmp(&other[..])
+    }
+}
+
+impl FromIterator<u8> for MBuf {
+    fn from_iter<I: IntoIterator<Item = u8>>(iter: I) -> Self {
+        let iter = iter.into_iter();
+        let mut buf = MBuf::with_capacity(iter.size_hint().0);
+        buf.extend(iter);
+        buf
+    }
+}
+
+impl<'a> FromIterator<&'a u8> for MBuf {
+    fn from_iter<I: IntoIterator<Item = &'a u8>>(iter: I) -> Self {
+        iter.into_iter().copied().collect()
+    }
+}
+
+impl Extend<u8> for MBuf {
+    fn extend<I: IntoIterator<Item = u8>>(&mut self, iter: I) {
+        let mut iter = iter.into_iter();
+        self.reserve(iter.size_hint().0);
+        let mut chunk = [0u8; 64];
+        loop {
+            let mut n = 0;
+            for byte in iter.by_ref().take(chunk.len()) {
+                chunk[n] = byte;
+                n += 1;
+            }
+            if n == 0 {
+                break;
+            }
+            self.append(&chunk[..n]);
+        }
+    }
+}
+
+impl<'a> Extend<&'a u8> for MBuf {
+    fn extend<I: IntoIterator<Item = &'a u8>>(&mut self, iter: I) {
+        self.extend(iter.into_iter().copied());
+    }
+}
+
+impl Read for Cursor<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let n = std::cmp::min(buf.len(), self.remaining());
+        buf[..n].copy_from_slice(&self.remaining_slice()[..n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
 impl Read for MBuf {
     fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
-        let len = self.len;
-        let to_read = std::cmp::min(buf.len(), len);
-        
-        unsafe {
-            ptr::copy_nonoverlapping(
-                self.data,
-                buf.as_mut_ptr(),
-                to_read
-            );
-            ptr::copy(
-                self.data.add(to_read),
-                self.data,
-                len - to_read
-            );
-        }
-        
-        self.len = len - to_read;
+        let to_read = std::cmp::min(buf.len(), self.len);
+        buf[..to_read].copy_from_slice(&self[..to_read]);
+        self.advance(to_read);
         Ok(to_read)
     }
 }
@@ -263,6 +925,15 @@ impl Write for MBuf {
         let written = self.append(buf);
         Ok(written)
     }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> Result<usize> {
+        let total = bufs.iter().map(|buf| buf.len()).sum();
+        self.reserve(total);
+        for buf in bufs {
+            self.append(buf);
+        }
+        Ok(total)
+    }
     
     fn flush(&mut self) -> Result<()> {
         Ok(())
@@ -302,6 +973,27 @@ mod tests {
         cursor.reset();
         assert_eq!(cursor.position(), 0);
         assert_eq!(cursor.next_slice(data.len()), Some(data.as_ref()));
+
+        cursor.reset();
+        let mut out = Vec::new();
+        cursor.read_to_end(&mut out).unwrap();
+        assert_eq!(out, data);
+        assert_eq!(buf.len(), data.len());
+    }
+    
+    #[test]
+    fn test_cursor_typed_reads() {
+        let mut buf = MBuf::with_capacity(16);
+        buf.append(&[0x12, 0x34, 0x12, 0x34, 0, 0, 0, 1, 0xff]);
+
+        let mut cursor = Cursor::new(&buf);
+        assert_eq!(cursor.read_u16_be(), Some(0x1234));
+        assert_eq!(cursor.read_u16_le(), Some(0x3412));
+        assert_eq!(cursor.read_u32_be(), Some(1));
+        assert_eq!(cursor.remaining(), 1);
+        assert_eq!(cursor.read_u16_be(), None);
+        assert_eq!(cursor.position(), 8);
+        assert_eq!(cursor.read_array::<1>(), Some([0xff]));
     }
     
     #[test]
@@ -317,6 +1009,158 @@ mod tests {
         assert_eq!(buf.len(), 0);
     }
     
+    #[test]
+    fn test_copy_on_write() {
+        let mut buf = MBuf::with_capacity(8);
+        buf.append(b"abcd");
+        let shared = buf.clone();
+
+        buf.advance(1);
+        assert_eq!(&*buf, b"bcd");
+        assert_eq!(buf.headroom(), 1);
+        assert_eq!(buf.tailroom(), 4);
+
+        // 共享时追加先复制，原数据保持不变
+        buf.append(b"e");
+        assert!(buf.is_unique());
+        assert_eq!(&*buf, b"bcde");
+        assert_eq!(&*shared, b"abcd");
+
+        let mut other = shared.clone();
+        other.make_unique();
+        other[0] = b'z';
+        assert_eq!(&*shared, b"abcd");
+        assert_eq!(&*other, b"zbcd");
+    }
+    
+    #[test]
+    fn test_slice() {
+        let mut buf = MBuf::with_capacity(16);
+        buf.append(b"hello world");
+
+        let word = buf.slice(6..);
+        assert_eq!(&*word, b"world");
+        assert_eq!(word.as_ptr(), buf[6..].as_ptr());
+        assert!(!buf.is_unique());
+        assert_eq!(&*word.slice(1..=2), b"or");
+        drop(word);
+        assert!(buf.is_unique());
+
+        let hello = buf.split_to(5);
+        assert_eq!(&*hello, b"hello");
+        assert_eq!(&*buf, b" world");
+        assert_eq!(hello.as_ptr().wrapping_add(5), buf.as_ptr());
+    }
+    
+    #[test]
+    fn test_content_traits() {
+        use std::collections::HashMap;
+
+        let a: MBuf = b"key".iter().collect();
+        let mut b = MBuf::with_capacity(8);
+        b.reserve_headroom(2);
+        b.extend(*b"key");
+        assert_eq!(a, b);
+        assert_eq!(a, b"key");
+        assert_eq!(a, &b"key"[..]);
+        assert!(a < b.slice(..2).iter().chain(b"z").copied().collect());
+
+        let mut map = HashMap::new();
+        map.insert(a, 1);
+        assert_eq!(map.get(&b), Some(&1));
+        assert_eq!(map.get(&b"key"[..]), Some(&1));
+
+        let long: MBuf = (0..=255u8).cycle().take(1000).collect();
+        assert_eq!(long.len(), 1000);
+        assert_eq!(long[999], (999 % 256) as u8);
+    }
+
+    #[test]
+    fn test_secure() {
+        let mut buf = MBuf::with_capacity(8);
+        buf.append(b"secret");
+        let shared = buf.clone();
+        assert!(!shared.is_secure());
+        // 设置作用于共享这块内存的所有引用
+        let mut other = shared.slice(..3);
+        other.set_secure();
+        assert!(buf.is_secure());
+        assert!(shared.is_secure());
+        drop(other);
+
+        // 共享时clear不能清零其他引用仍在读取的数据
+        buf.clear();
+        assert_eq!(shared, b"secret");
+        drop(shared);
+
+        buf.append(b"key");
+        buf.clear();
+        assert!(buf.spare_capacity_mut().iter().all(|&b| b == 0));
+
+        buf.append(b"grow");
+        buf.resize(64);
+        assert_eq!(buf, b"grow");
+
+        match buf.lock_memory() {
+            Ok(()) => {
+                buf.resize(4096);
+                assert_eq!(buf, b"grow");
+            }
+            // 环境不允许锁定内存
+            Err(e) => assert!(e.raw_os_error().is_some() || e.kind() == std::io::ErrorKind::Unsupported),
+        }
+    }
+
+    #[test]
+    fn test_locked_memory_is_page_aligned() {
+        let page = super::page_size();
+        let mut a = MBuf::with_capacity(100);
+        a.append(b"first");
+        let mut b = MBuf::with_capacity(100);
+        // 锁定失败时内存同样换成整页分配
+        let _ = a.lock_memory();
+        let _ = b.lock_memory();
+        assert_eq!(a, b"first");
+        assert_eq!(a.capacity(), 100);
+        assert_eq!(a.data as usize % page, 0);
+        assert_eq!(b.data as usize % page, 0);
+        assert_ne!(a.data as usize / page, b.data as usize / page);
+
+        a.resize(200);
+        assert_eq!(a, b"first");
+        assert_eq!(a.data as usize % page, 0);
+    }
+
+    #[test]
+    fn test_write_vectored() {
+        let mut buf = MBuf::with_capacity(4);
+        let parts = [IoSlice::new(b"vec"), IoSlice::new(b""), IoSlice::new(b"tored")];
+        assert_eq!(buf.write_vectored(&parts).unwrap(), 8);
+        assert_eq!(&*buf, b"vectored");
+
+        let spare = buf.spare_capacity_mut();
+        let n = spare.len().min(3);
+        spare[..n].copy_from_slice(&b"!!!"[..n]);
+        buf.commit(n);
+        assert_eq!(&buf[8..], &b"!!!"[..n]);
+    }
+    
+    #[test]
+    fn test_prepend() {
+        let mut buf = MBuf::with_capacity(16);
+        buf.reserve_headroom(4);
+        buf.append(b"data");
+        buf.prepend(b"hd");
+        assert_eq!(&*buf, b"hddata");
+        assert_eq!(buf.headroom(), 2);
+        assert_eq!(buf.capacity(), 16);
+
+        // headroom不足时重新分配
+        buf.prepend(b"long");
+        assert_eq!(&*buf, b"longhddata");
+        assert_eq!(buf.headroom(), 0);
+    }
+    
     #[test]
     fn test_write() {
         let mut buf = MBuf::with_capacity(1024);
@@ -20,15 +20,148 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
-use std::sync::Mutex;
+use std::any::TypeId;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex};
 use std::sync::atomic::{AtomicUsize, Ordering};
 use super::MBuf;
+use crate::mbuf::Private;
 
 /// MBuf池结构体
+///
+/// 池分配的MBuf记住来源池，最后一个克隆或切片释放时内存自动回到池中
 pub struct MPool {
+    inner: Arc<PoolInner>,
+}
+
+/// 超过池容量的MBuf（如`resize`扩容过的）回到池中时的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OversizePolicy {
+    /// 原样放回空闲列表
+    Keep,
+    /// 重新分配为池容量后放回
+    #[default]
+    Shrink,
+    /// 释放内存，不放回
+    Discard,
+}
+
+/// MPool回收缓冲区的统计
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PoolStats {
+    /// 放回空闲列表的数量，包括缩小后放回的
+    pub recycled: usize,
+    /// 超过池容量、缩小后放回的数量
+    pub shrunk: usize,
+    /// 超过池容量被丢弃的数量
+    pub discarded: usize,
+    /// 交给`free`的其他池分配的MBuf数量，这些内存回到各自的池中
+    pub foreign: usize,
+    /// 交给`free`的不属于任何池的MBuf数量，这些内存直接释放
+    pub unpooled: usize,
+}
+
+#[derive(Default)]
+struct Counters {
+    recycled: AtomicUsize,
+    shrunk: AtomicUsize,
+    discarded: AtomicUsize,
+    foreign: AtomicUsize,
+    unpooled: AtomicUsize,
+}
+
+/// 分配位置，代替调用者分配缓冲区的对象在创建时记录，之后的分配都记在创建它的位置
+///
+/// 未启用`tracking`特性时不占空间
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct AllocSite {
+    #[cfg(feature = "tracking")]
+    location: &'static std::panic::Location<'static>,
+}
+
+impl AllocSite {
+    /// 记录调用者的位置
+    #[cfg_attr(feature = "tracking", track_caller)]
+    pub(crate) fn caller() -> Self {
+        Self {
+            #[cfg(feature = "tracking")]
+            location: std::panic::Location::caller(),
+        }
+    }
+}
+
+/// 池的共享状态，MBuf通过Weak引用指向它
+pub(crate) struct PoolInner {
     free_list: Mutex<Vec<MBuf>>,
     allocated_count: AtomicUsize,
     capacity: usize,
+    oversize: OversizePolicy,
+    counters: Counters,
+    private: Option<(TypeId, fn() -> Private)>,
+    secure: bool,
+    mlock: bool,
+    #[cfg(feature = "tracking")]
+    tracker: crate::tracking::Tracker,
+}
+
+impl PoolInner {
+    /// 按池的设置创建新的MBuf
+    fn create(&self) -> MBuf {
+        let mut buf = MBuf::with_capacity(self.capacity);
+        buf.set_private(self.private.map(|(_, init)| init()));
+        if self.secure {
+            buf.set_secure();
+        }
+        if self.mlock {
+            let _ = buf.lock_memory();
+        }
+        buf
+    }
+
+    /// 池分配的内存在最后一个引用释放时交还到这里
+    pub(crate) fn reclaim(&self, buf: MBuf) {
+        self.recycle(buf);
+        self.allocated_count.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// 整理独占的MBuf并放入空闲列表，超过池容量的按OversizePolicy处理
+    fn recycle(&self, mut buf: MBuf) {
+        #[cfg(feature = "tracking")]
+        if let Some(id) = buf.take_track_id() {
+            self.tracker.forget(id);
+        }
+        buf.reset();
+        if buf.capacity() > self.capacity {
+            match self.oversize {
+                OversizePolicy::Keep => {}
+                OversizePolicy::Shrink => {
+                    buf.shrink_to(self.capacity);
+                    self.counters.shrunk.fetch_add(1, Ordering::Relaxed);
+                }
+                OversizePolicy::Discard => {
+                    self.counters.discarded.fetch_add(1, Ordering::Relaxed);
+                    return;
+                }
+            }
+        }
+        // 重新初始化私有区，类型不符时替换为本池的私有区类型
+        match self.private {
+            Some((type_id, _)) if buf.private_type_id() == Some(type_id) => buf.reset_private(),
+            Some((_, init)) => buf.set_private(Some(init())),
+            None => buf.set_private(None),
+        }
+        if self.secure {
+            buf.set_secure();
+        }
+        if buf.is_secure() {
+            buf.wipe();
+        }
+        #[cfg(feature = "debug-guards")]
+        buf.guard_on_free();
+        self.free_list.lock().unwrap().push(buf);
+        self.counters.recycled.fetch_add(1, Ordering::Relaxed);
+    }
 }
 
 impl MPool {
@@ -39,46 +172,221 @@ impl MPool {
             free_list.push(MBuf::with_capacity(capacity));
         }
 
-        Self {
+        let inner = PoolInner {
             free_list: Mutex::new(free_list),
             allocated_count: AtomicUsize::new(0),
             capacity,
+            oversize: OversizePolicy::default(),
+            counters: Counters::default(),
+            private: None,
+            secure: false,
+            mlock: false,
+            #[cfg(feature = "tracking")]
+            tracker: Default::default(),
+        };
+        Self { inner: Arc::new(inner) }
+    }
+
+    /// 配置池时访问内部状态，此时还没有分配出去的MBuf
+    fn inner_mut(&mut self) -> &mut PoolInner {
+        Arc::get_mut(&mut self.inner).expect("MPool must be configured before allocating")
+    }
+
+    /// 为池中每个MBuf配置类型为P的私有区，通过`MBuf::private::<P>()`访问，
+    /// 归还到池中时重新初始化为`P::default()`
+    pub fn with_private<P: Default + Send + Sync + 'static>(mut self) -> Self {
+        let inner = self.inner_mut();
+        inner.private = Some((TypeId::of::<P>(), Private::new::<P>));
+        for buf in inner.free_list.get_mut().unwrap().iter_mut() {
+            buf.set_private(Some(Private::new::<P>()));
+        }
+        self
+    }
+
+    /// 池中的MBuf都处于secure模式，释放回池中时内存被清零
+    pub fn with_secure(mut self) -> Self {
+        let inner = self.inner_mut();
+        inner.secure = true;
+        for buf in inner.free_list.get_mut().unwrap().iter_mut() {
+            buf.set_secure();
+        }
+        self
+    }
+
+    /// 在secure模式的基础上用mlock锁定池中MBuf的内存，锁定失败时（如超出RLIMIT_MEMLOCK）仍正常使用
+    pub fn with_mlock(mut self) -> Self {
+        self = self.with_secure();
+        let inner = self.inner_mut();
+        inner.mlock = true;
+        for buf in inner.free_list.get_mut().unwrap().iter_mut() {
+            let _ = buf.lock_memory();
         }
+        self
     }
 
-    /// 从池中分配一个MBuf
+    /// 设置超过池容量的MBuf回到池中时的处理方式，默认为`OversizePolicy::Shrink`
+    pub fn with_oversize_policy(mut self, policy: OversizePolicy) -> Self {
+        self.inner_mut().oversize = policy;
+        self
+    }
+
+    /// 从池中分配一个空的MBuf
+    ///
+    /// 启用`tracking`特性时记录调用位置，直到内存回到池中
+    #[cfg_attr(feature = "tracking", track_caller)]
     pub fn alloc(&self) -> MBuf {
-        let mut free_list = self.free_list.lock().unwrap();
-        self.allocated_count.fetch_add(1, Ordering::Relaxed);
+        self.alloc_at(AllocSite::caller())
+    }
 
-        if let Some(buf) = free_list.pop() {
-            return buf;
+    /// 分配并记录为site处的分配，用于分配发生在闭包、迭代器或第三方代码中、
+    /// `track_caller`无法传递调用位置的情况
+    pub(crate) fn alloc_at(&self, site: AllocSite) -> MBuf {
+        #[cfg(feature = "tracking")]
+        {
+            let mut buf = self.take();
+            buf.set_track_id(self.inner.tracker.record(site.location));
+            buf
         }
+        #[cfg(not(feature = "tracking"))]
+        {
+            let _ = site;
+            self.take()
+        }
+    }
+
+    fn take(&self) -> MBuf {
+        let mut free_list = self.inner.free_list.lock().unwrap();
+        self.inner.allocated_count.fetch_add(1, Ordering::Relaxed);
 
         // 如果空闲列表为空，创建新的MBuf
-        MBuf::with_capacity(self.capacity)
+        let buf = free_list.pop();
+        drop(free_list);
+        let mut buf = buf.unwrap_or_else(|| self.inner.create());
+        buf.set_origin(Arc::downgrade(&self.inner));
+        buf
+    }
+
+    /// 从池中分配一个MBuf，离开作用域时自动归还
+    #[cfg_attr(feature = "tracking", track_caller)]
+    pub fn get(&self) -> PooledMBuf<&MPool> {
+        PooledMBuf::new(self)
+    }
+
+    /// 同`get`，但持有池的Arc，可以在线程或任务间传递
+    #[cfg_attr(feature = "tracking", track_caller)]
+    pub fn get_owned(self: &Arc<Self>) -> PooledMBuf<Arc<MPool>> {
+        PooledMBuf::new(self.clone())
     }
 
     /// 将MBuf释放回池中
+    ///
+    /// 只释放这一个引用，其他克隆或切片仍在使用时，内存等到最后一个引用释放才回到池中；
+    /// 回到池中时长度、偏移和元数据被重置。其他池分配的MBuf回到自己的池并计入`PoolStats::foreign`，
+    /// 不属于任何池的MBuf直接释放并计入`PoolStats::unpooled`
     pub fn free(&self, buf: MBuf) {
-        let mut free_list = self.free_list.lock().unwrap();
-        free_list.push(buf);
-        self.allocated_count.fetch_sub(1, Ordering::Relaxed);
+        match buf.is_pooled_by(&self.inner) {
+            Some(true) => {}
+            Some(false) => {
+                self.inner.counters.foreign.fetch_add(1, Ordering::Relaxed);
+            }
+            None => {
+                self.inner.counters.unpooled.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        drop(buf);
+    }
+
+    /// 获取池中每个MBuf的容量
+    pub fn capacity(&self) -> usize {
+        self.inner.capacity
+    }
+
+    /// 获取回收缓冲区的统计
+    pub fn stats(&self) -> PoolStats {
+        let counters = &self.inner.counters;
+        PoolStats {
+            recycled: counters.recycled.load(Ordering::Relaxed),
+            shrunk: counters.shrunk.load(Ordering::Relaxed),
+            discarded: counters.discarded.load(Ordering::Relaxed),
+            foreign: counters.foreign.load(Ordering::Relaxed),
+            unpooled: counters.unpooled.load(Ordering::Relaxed),
+        }
     }
 
     /// 获取当前分配的MBuf数量
     pub fn allocated_count(&self) -> usize {
-        self.allocated_count.load(Ordering::Relaxed)
+        self.inner.allocated_count.load(Ordering::Relaxed)
+    }
+
+    /// 按分配位置汇总尚未归还的MBuf，最长未归还的位置排在最前
+    #[cfg(feature = "tracking")]
+    pub fn outstanding_report(&self) -> crate::tracking::OutstandingReport {
+        self.inner.tracker.report()
     }
 
     /// 获取当前空闲的MBuf数量
     pub fn free_count(&self) -> usize {
-        let a =self.free_list.lock().unwrap().len();
+        let a =self.inner.free_list.lock().unwrap().len();
         println!("free_count: {}", a);
         a
     }
 }
 
+/// 从MPool分配的MBuf，drop时（包括提前返回和panic时）自动归还到池中
+///
+/// P可以是`&MPool`或`Arc<MPool>`
+pub struct PooledMBuf<P: Deref<Target = MPool>> {
+    buf: Option<MBuf>,
+    pool: P,
+}
+
+impl<P: Deref<Target = MPool>> PooledMBuf<P> {
+    /// 从pool分配一个MBuf
+    #[cfg_attr(feature = "tracking", track_caller)]
+    pub fn new(pool: P) -> Self {
+        let buf = pool.alloc();
+        Self { buf: Some(buf), pool }
+    }
+
+    /// 所属的池
+    pub fn pool(&self) -> &MPool {
+        &self.pool
+    }
+
+    /// 取出MBuf，守卫不再在作用域结束时归还；MBuf仍属于该池，最后一个引用drop时回到池中
+    pub fn detach(mut self) -> MBuf {
+        self.buf.take().unwrap()
+    }
+}
+
+impl<P: Deref<Target = MPool>> Deref for PooledMBuf<P> {
+    type Target = MBuf;
+
+    fn deref(&self) -> &MBuf {
+        self.buf.as_ref().unwrap()
+    }
+}
+
+impl<P: Deref<Target = MPool>> DerefMut for PooledMBuf<P> {
+    fn deref_mut(&mut self) -> &mut MBuf {
+        self.buf.as_mut().unwrap()
+    }
+}
+
+impl<P: Deref<Target = MPool>> Drop for PooledMBuf<P> {
+    fn drop(&mut self) {
+        if let Some(buf) = self.buf.take() {
+            self.pool.free(buf);
+        }
+    }
+}
+
+impl<P: Deref<Target = MPool>> fmt::Debug for PooledMBuf<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -105,4 +413,240 @@ mod tests {
         assert_eq!(pool.free_count(), 2);
         assert_eq!(pool.allocated_count(), 0);
     }
-}
\ No newline at end of file
+
+    #[derive(Default)]
+    struct PacketState {
+        hits: u32,
+    }
+
+    #[test]
+    fn test_private_area() {
+        let pool = MPool::new(1, 64).with_private::<PacketState>();
+
+        let mut buf = pool.alloc();
+        assert!(buf.private::<u64>().is_none());
+        buf.private_mut::<PacketState>().unwrap().hits = 3;
+        assert_eq!(buf.private::<PacketState>().unwrap().hits, 3);
+
+        // 克隆得到独立的默认私有区
+        let copy = buf.clone();
+        assert_eq!(copy.private::<PacketState>().unwrap().hits, 0);
+        drop(copy);
+
+        pool.free(buf);
+        let buf = pool.alloc();
+        assert_eq!(buf.private::<PacketState>().unwrap().hits, 0);
+
+        // 池中新建的MBuf同样带有私有区
+        let extra = pool.alloc();
+        assert!(extra.private::<PacketState>().is_some());
+        pool.free(buf);
+        pool.free(extra);
+    }
+
+    #[test]
+    fn test_secure_pool() {
+        let pool = MPool::new(1, 16).with_secure();
+        let mut buf = pool.alloc();
+        assert!(buf.is_secure());
+        buf.append(b"password");
+        buf.advance(4);
+        pool.free(buf);
+
+        // 归还时整块内存已清零，包括已前移越过的数据
+        let mut buf = pool.alloc();
+        buf.truncate(0);
+        buf.reserve_headroom(0);
+        assert!(buf.spare_capacity_mut().iter().all(|&b| b == 0));
+        pool.free(buf);
+    }
+
+    #[test]
+    fn test_lock_memory_wipes_old_memory() {
+        let pool = MPool::new(1, 16);
+        let mut buf = pool.alloc();
+        buf.append(b"key");
+        let sibling = buf.clone();
+        // 锁定失败时内存同样换成整页分配
+        let _ = buf.lock_memory();
+        assert_eq!(buf, b"key");
+        assert_eq!(pool.allocated_count(), 1);
+
+        // 留在旧内存上的克隆释放后，旧内存回到池中时已清零
+        drop(sibling);
+        assert_eq!(pool.allocated_count(), 0);
+        let mut old = pool.alloc();
+        assert!(old.spare_capacity_mut().iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn test_pooled_mbuf() {
+        let pool = MPool::new(1, 64);
+        {
+            let mut buf = pool.get();
+            buf.append(b"hello");
+            assert_eq!(&buf[..], b"hello");
+            assert_eq!(pool.allocated_count(), 1);
+        }
+        assert_eq!(pool.allocated_count(), 0);
+        assert_eq!(pool.free_count(), 1);
+
+        // panic时同样归还
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _buf = pool.get();
+            panic!("early exit");
+        }));
+        assert!(result.is_err());
+        assert_eq!(pool.allocated_count(), 0);
+
+        let buf = pool.get().detach();
+        assert_eq!(pool.allocated_count(), 1);
+        pool.free(buf);
+        assert_eq!(pool.allocated_count(), 0);
+
+        let buf = pool.get().detach();
+        let clone = buf.clone();
+        drop(buf);
+        assert_eq!(pool.allocated_count(), 1);
+        drop(clone);
+        assert_eq!(pool.allocated_count(), 0);
+    }
+
+    #[test]
+    fn test_pooled_mbuf_owned() {
+        let pool = Arc::new(MPool::new(0, 64));
+        let mut buf = pool.get_owned();
+        buf.append(b"moved");
+        let handle = std::thread::spawn(move || {
+            assert_eq!(&buf[..], b"moved");
+            assert_eq!(buf.pool().capacity(), 64);
+        });
+        handle.join().unwrap();
+        assert_eq!(pool.allocated_count(), 0);
+        assert_eq!(pool.free_count(), 1);
+    }
+
+    #[test]
+    fn test_shared_buffer_recycled_on_last_drop() {
+        let pool = MPool::new(0, 64);
+        let mut buf = pool.alloc();
+        buf.append(b"header payload");
+        let head = buf.split_to(7);
+        let clone = buf.clone();
+
+        // 其他引用仍在使用，free只释放一个引用
+        pool.free(buf);
+        assert_eq!(pool.free_count(), 0);
+        assert_eq!(pool.allocated_count(), 1);
+        let other = pool.alloc();
+        assert_eq!(&head[..], b"header ");
+        assert_eq!(&clone[..], b"payload");
+
+        drop(head);
+        pool.free(clone);
+        assert_eq!(pool.free_count(), 1);
+        assert_eq!(pool.allocated_count(), 1);
+        pool.free(other);
+        assert_eq!(pool.allocated_count(), 0);
+
+        // 回到池中的缓冲区是空的
+        let buf = pool.alloc();
+        assert!(buf.is_empty());
+        assert_eq!(buf.headroom(), 0);
+        pool.free(buf);
+    }
+
+    #[test]
+    fn test_dropped_buffer_returns_to_pool() {
+        let pool = MPool::new(0, 64);
+        drop(pool.alloc());
+        assert_eq!(pool.allocated_count(), 0);
+        assert_eq!(pool.free_count(), 1);
+
+        // 池已销毁时内存直接释放
+        let buf = {
+            let pool = MPool::new(0, 64);
+            pool.alloc()
+        };
+        drop(buf);
+    }
+
+    #[test]
+    fn test_slice_across_threads() {
+        let pool = Arc::new(MPool::new(0, 64));
+        let mut buf = pool.alloc();
+        buf.append(b"shared");
+        let slice = buf.slice(..);
+        pool.free(buf);
+        let reader = std::thread::spawn(move || slice.to_vec());
+        assert_eq!(reader.join().unwrap(), b"shared");
+        assert_eq!(pool.allocated_count(), 0);
+        assert_eq!(pool.free_count(), 1);
+    }
+
+    #[test]
+    fn test_foreign_and_unpooled_buffers() {
+        let pool = MPool::new(0, 64);
+        // 不属于任何池的MBuf直接释放
+        pool.free(MBuf::with_capacity(64));
+        pool.free(MBuf::with_capacity(16));
+        assert_eq!(pool.free_count(), 0);
+        assert_eq!(pool.allocated_count(), 0);
+        assert_eq!(pool.stats(), PoolStats { unpooled: 2, ..Default::default() });
+
+        // 其他池分配的MBuf回到自己的池
+        let other = MPool::new(0, 64);
+        pool.free(other.alloc());
+        assert_eq!(pool.free_count(), 0);
+        assert_eq!(other.free_count(), 1);
+        assert_eq!(other.allocated_count(), 0);
+        assert_eq!(pool.stats(), PoolStats { foreign: 1, unpooled: 2, ..Default::default() });
+    }
+
+    #[test]
+    fn test_returned_buffer_normalised() {
+        let pool = MPool::new(0, 64);
+        let mut buf = pool.alloc();
+        buf.reserve_headroom(8);
+        buf.append(b"data");
+        buf.meta_mut().timestamp = Some(std::time::Duration::from_secs(1));
+        pool.free(buf);
+
+        let buf = pool.alloc();
+        assert!(buf.is_empty());
+        assert_eq!(buf.headroom(), 0);
+        assert_eq!(buf.meta().timestamp, None);
+        pool.free(buf);
+        assert_eq!(pool.stats().recycled, 2);
+    }
+
+    #[test]
+    fn test_oversize_policy() {
+        let grow = |pool: &MPool| {
+            let mut buf = pool.alloc();
+            buf.append(b"data");
+            buf.resize(1 << 20);
+            pool.free(buf);
+            pool.alloc()
+        };
+
+        let pool = MPool::new(0, 64);
+        let buf = grow(&pool);
+        assert_eq!(buf.capacity(), 64);
+        assert!(buf.is_empty());
+        pool.free(buf);
+        assert_eq!(pool.stats().shrunk, 1);
+
+        let pool = MPool::new(0, 64).with_oversize_policy(OversizePolicy::Keep);
+        let buf = grow(&pool);
+        assert_eq!(buf.capacity(), 1 << 20);
+        pool.free(buf);
+
+        let pool = MPool::new(0, 64).with_oversize_policy(OversizePolicy::Discard);
+        let buf = grow(&pool);
+        assert_eq!(buf.capacity(), 64);
+        pool.free(buf);
+        assert_eq!(pool.stats(), PoolStats { recycled: 1, discarded: 1, ..Default::default() });
+        assert_eq!(pool.allocated_count(), 0);
+    }
+}
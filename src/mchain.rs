@@ -0,0 +1,886 @@
+// MIT License
+//
+// Copyright (c) 2023 gaosg
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::collections::vec_deque::{self, VecDeque};
+use std::io::{self, IoSlice, IoSliceMut, Read, Write};
+use std::ops::RangeBounds;
+use std::task::Poll;
+use super::{MBuf, MPool};
+use crate::mbuf::{bounds, impl_typed_reads};
+
+/// 单次向量I/O最多使用的IoSlice数量，与Linux的IOV_MAX一致
+pub const IOV_MAX: usize = 1024;
+
+/// write_to每轮在栈上准备的IoSlice数量
+pub(crate) const IOV_BATCH: usize = 64;
+
+/// 由多个MBuf分段组成的消息链，类似BSD mbuf的m_next链表
+///
+/// 分段通过移动或克隆（共享引用计数）加入链中，不会复制数据
+#[derive(Clone, Default)]
+pub struct MChain {
+    segs: VecDeque<MBuf>,
+    len: usize,
+}
+
+impl MChain {
+    /// 创建空的消息链
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 获取链中所有分段的数据总长度
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// 是否没有数据
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// 获取分段数量
+    pub fn segment_count(&self) -> usize {
+        self.segs.len()
+    }
+
+    /// 在链尾追加分段
+    pub fn push_back(&mut self, seg: MBuf) {
+        self.len += seg.len();
+        self.segs.push_back(seg);
+    }
+
+    /// 在链头插入分段，例如在负载前加上协议头
+    pub fn push_front(&mut self, seg: MBuf) {
+        self.len += seg.len();
+        self.segs.push_front(seg);
+    }
+
+    /// 移除并返回第一个分段
+    pub fn pop_front(&mut self) -> Option<MBuf> {
+        let seg = self.segs.pop_front()?;
+        self.len -= seg.len();
+        Some(seg)
+    }
+
+    /// 移除并返回最后一个分段
+    pub fn pop_back(&mut self) -> Option<MBuf> {
+        let seg = self.segs.pop_back()?;
+        self.len -= seg.len();
+        Some(seg)
+    }
+
+    /// 获取第一个分段
+    pub fn front(&self) -> Option<&MBuf> {
+        self.segs.front()
+    }
+
+    /// 获取最后一个分段
+    pub fn back(&self) -> Option<&MBuf> {
+        self.segs.back()
+    }
+
+    /// 获取指定下标的分段
+    pub fn segment(&self, index: usize) -> Option<&MBuf> {
+        self.segs.get(index)
+    }
+
+    /// 遍历所有分段
+    pub fn segments(&self) -> Segments<'_> {
+        Segments { inner: self.segs.iter() }
+    }
+
+    /// 获取指定偏移处的字节，越界返回None
+    pub fn get(&self, mut offset: usize) -> Option<u8> {
+        for seg in &self.segs {
+            if offset < seg.len() {
+                return Some(seg[offset]);
+            }
+            offset -= seg.len();
+        }
+        None
+    }
+
+    /// 按顺序遍历链中的所有字节
+    pub fn bytes(&self) -> impl Iterator<Item = u8> + '_ {
+        self.segs.iter().flat_map(|seg| seg.iter().copied())
+    }
+
+    /// 将另一条链的所有分段移到本链尾部
+    pub fn append(&mut self, other: &mut MChain) {
+        self.len += other.len;
+        self.segs.append(&mut other.segs);
+        other.len = 0;
+    }
+
+    /// 将所有数据复制到一个连续的Vec中
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.len);
+        for seg in &self.segs {
+            out.extend_from_slice(seg);
+        }
+        out
+    }
+
+    /// 移除所有分段
+    pub fn clear(&mut self) {
+        self.segs.clear();
+        self.len = 0;
+    }
+
+    /// 在链尾追加数据：优先写入最后一个独占分段的剩余空间，不足时追加新分段
+    pub fn extend_from_slice(&mut self, data: &[u8]) {
+        let mut rest = data;
+        if let Some(last) = self.segs.back_mut() {
+            if last.is_unique() {
+                let n = rest.len().min(last.tailroom());
+                last.append(&rest[..n]);
+                rest = &rest[n..];
+            }
+        }
+        if !rest.is_empty() {
+            let mut seg = MBuf::with_capacity(rest.len());
+            seg.append(rest);
+            self.segs.push_back(seg);
+        }
+        self.len += data.len();
+    }
+
+    /// 使前n个字节在首个分段中连续存放（m_pullup），以便直接按头部结构访问
+    ///
+    /// 首个分段与其他引用共享内存时先复制；链中数据不足n字节时返回false且不修改链
+    pub fn pullup(&mut self, n: usize) -> bool {
+        if n > self.len {
+            return false;
+        }
+        let head_len = self.segs.front().map_or(0, MBuf::len);
+        if n <= head_len {
+            return true;
+        }
+
+        let mut head = self.segs.pop_front().unwrap();
+        head.reserve(n - head.len());
+        while head.len() < n {
+            let seg = self.segs.front_mut().unwrap();
+            let take = seg.len().min(n - head.len());
+            head.append(&seg[..take]);
+            seg.advance(take);
+            if seg.is_empty() {
+                self.segs.pop_front();
+            }
+        }
+        self.segs.push_front(head);
+        true
+    }
+
+    /// 复制从offset开始的len个字节到dst（m_copydata），范围越界或dst不足len字节时返回false
+    pub fn copy_data(&self, offset: usize, len: usize, dst: &mut [u8]) -> bool {
+        if offset > self.len || len > self.len - offset || dst.len() < len {
+            return false;
+        }
+
+        let mut copied = 0;
+        let mut pos = 0;
+        for seg in &self.segs {
+            if copied == len {
+                break;
+            }
+            let start = offset + copied;
+            if start < pos + seg.len() {
+                let seg_off = start - pos;
+                let n = (seg.len() - seg_off).min(len - copied);
+                dst[copied..copied + n].copy_from_slice(&seg[seg_off..seg_off + n]);
+                copied += n;
+            }
+            pos += seg.len();
+        }
+        true
+    }
+
+    /// 用src覆盖从offset开始的数据（m_copyback），超出链尾时扩展链，中间的空缺以0填充
+    ///
+    /// 被覆盖的分段与其他引用共享内存时先复制，不影响其他引用看到的数据
+    pub fn copy_back(&mut self, offset: usize, src: &[u8]) {
+        if offset > self.len {
+            let gap = vec![0u8; offset - self.len];
+            self.extend_from_slice(&gap);
+        }
+
+        let mut written = 0;
+        let mut pos = 0;
+        for seg in self.segs.iter_mut() {
+            if written == src.len() {
+                break;
+            }
+            let start = offset + written;
+            if start < pos + seg.len() {
+                let seg_off = start - pos;
+                let n = (seg.len() - seg_off).min(src.len() - written);
+                seg.make_unique();
+                seg[seg_off..seg_off + n].copy_from_slice(&src[written..written + n]);
+                written += n;
+            }
+            pos += seg.len();
+        }
+
+        if written < src.len() {
+            self.extend_from_slice(&src[written..]);
+        }
+    }
+
+    /// 返回指定范围数据组成的新链（m_copym），分段以零拷贝切片共享原链的内存
+    ///
+    /// # Panics
+    ///
+    /// 范围超出链中数据时panic
+    pub fn slice(&self, range: impl RangeBounds<usize>) -> MChain {
+        let (start, end) = bounds(range, self.len);
+        let mut out = MChain::new();
+        let mut pos = 0;
+        for seg in &self.segs {
+            let seg_end = pos + seg.len();
+            if seg_end > start && pos < end {
+                let from = start.saturating_sub(pos);
+                let to = seg.len().min(end - pos);
+                out.push_back(seg.slice(from..to));
+            }
+            if seg_end >= end {
+                break;
+            }
+            pos = seg_end;
+        }
+        out
+    }
+
+    /// 将所有数据合并到首个分段中，返回连续的数据
+    pub fn linearize(&mut self) -> &[u8] {
+        self.pullup(self.len);
+        self.segs.front().map_or(&[], |head| &head[..])
+    }
+
+    /// 把较小的分段复制合并到从池中分配的缓冲区中，减少分段数量；
+    /// 不小于池容量的分段保持零拷贝
+    #[cfg_attr(feature = "tracking", track_caller)]
+    pub fn defrag(&mut self, pool: &MPool) {
+        let mut out = MChain::new();
+        let mut current: Option<MBuf> = None;
+        for seg in std::mem::take(&mut self.segs) {
+            if seg.len() >= pool.capacity() {
+                if let Some(buf) = current.take() {
+                    out.push_back(buf);
+                }
+                out.push_back(seg);
+                continue;
+            }
+
+            let mut rest = &seg[..];
+            while !rest.is_empty() {
+                if current.is_none() {
+                    current = Some(pool.alloc());
+                }
+                let buf = current.as_mut().unwrap();
+                let n = rest.len().min(pool.capacity() - buf.len());
+                buf.append(&rest[..n]);
+                rest = &rest[n..];
+                if buf.len() == pool.capacity() {
+                    out.push_back(current.take().unwrap());
+                }
+            }
+        }
+        if let Some(buf) = current {
+            out.push_back(buf);
+        }
+        *self = out;
+    }
+
+    /// 丢弃开头的n个字节，完全消费的分段从链中移除
+    ///
+    /// # Panics
+    ///
+    /// n大于链中数据长度时panic
+    pub fn advance(&mut self, mut n: usize) {
+        assert!(n <= self.len, "advance {} beyond length {}", n, self.len);
+        self.len -= n;
+        while let Some(seg) = self.segs.front_mut() {
+            if n < seg.len() {
+                seg.advance(n);
+                break;
+            }
+            n -= seg.len();
+            self.segs.pop_front();
+        }
+    }
+
+    /// 将数据导出为IoSlice填入dst，返回使用的数量
+    ///
+    /// 同一块内存中前后相邻的分段合并为一个IoSlice，最多使用IOV_MAX个
+    pub fn as_io_slices<'a>(&'a self, dst: &mut [IoSlice<'a>]) -> usize {
+        let limit = dst.len().min(IOV_MAX);
+        let mut count = 0;
+        let mut segs = self.segs.iter().filter(|seg| !seg.is_empty()).peekable();
+        while count < limit {
+            let Some(first) = segs.next() else { break };
+            let mut last = first;
+            let mut len = first.len();
+            while let Some(next) = segs.next_if(|next| last.is_contiguous_with(next)) {
+                len += next.len();
+                last = next;
+            }
+            // 合并的分段位于同一块内存中，数据区间首尾相接
+            let slice = unsafe { std::slice::from_raw_parts(first.as_ptr(), len) };
+            dst[count] = IoSlice::new(slice);
+            count += 1;
+        }
+        count
+    }
+
+    /// 使用write_vectored把所有数据写入w，已写入的数据从链中移除，返回写入的字节数
+    ///
+    /// 发生错误时链中保留尚未写入的数据
+    pub fn write_to<W: Write>(&mut self, w: &mut W) -> io::Result<usize> {
+        let mut total = 0;
+        while !self.is_empty() {
+            match self.poll_write_batch(|iov| Poll::Ready(w.write_vectored(iov))) {
+                Poll::Ready(Ok(n)) => total += n,
+                Poll::Ready(Err(e)) if e.kind() == io::ErrorKind::Interrupted => {}
+                Poll::Ready(Err(e)) => return Err(e),
+                Poll::Pending => unreachable!(),
+            }
+        }
+        Ok(total)
+    }
+
+    /// 把最多IOV_BATCH个分段交给write做一次向量写，写出的数据从链中移除，返回写出的字节数
+    ///
+    /// 写出0字节时返回WriteZero错误
+    pub(crate) fn poll_write_batch<F>(&mut self, write: F) -> Poll<io::Result<usize>>
+    where
+        F: FnOnce(&[IoSlice<'_>]) -> Poll<io::Result<usize>>,
+    {
+        let mut iov = [IoSlice::new(&[]); IOV_BATCH];
+        let count = self.as_io_slices(&mut iov);
+        let n = match write(&iov[..count]) {
+            Poll::Ready(Ok(0)) => return Poll::Ready(Err(io::ErrorKind::WriteZero.into())),
+            Poll::Ready(Ok(n)) => n,
+            other => return other,
+        };
+        self.advance(n);
+        Poll::Ready(Ok(n))
+    }
+
+    /// 从池中分配最多max_bufs个缓冲区，通过read_vectored把数据分散读入它们的空闲空间，
+    /// 读到数据的缓冲区追加到链尾，其余归还池中，返回读取的字节数
+    #[cfg_attr(feature = "tracking", track_caller)]
+    pub fn read_vectored_from<R: Read>(&mut self, r: &mut R, pool: &MPool, max_bufs: usize) -> io::Result<usize> {
+        let count = max_bufs.clamp(1, IOV_MAX);
+        let mut bufs = Vec::with_capacity(count);
+        for _ in 0..count {
+            bufs.push(pool.alloc());
+        }
+        let result = {
+            let mut iov: Vec<IoSliceMut<'_>> = bufs
+                .iter_mut()
+                .map(|buf| IoSliceMut::new(buf.spare_capacity_mut()))
+                .collect();
+            r.read_vectored(&mut iov)
+        };
+
+        let mut rest = *result.as_ref().unwrap_or(&0);
+        for mut buf in bufs {
+            let n = rest.min(buf.tailroom());
+            if n == 0 {
+                pool.free(buf);
+                continue;
+            }
+            buf.commit(n);
+            rest -= n;
+            self.push_back(buf);
+        }
+        result
+    }
+
+    /// 创建跨分段读取的游标
+    pub fn cursor(&self) -> ChainCursor<'_> {
+        ChainCursor::new(self)
+    }
+}
+
+impl Write for MChain {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        let mut total = 0;
+        for buf in bufs {
+            self.extend_from_slice(buf);
+            total += buf.len();
+        }
+        Ok(total)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl From<MBuf> for MChain {
+    fn from(seg: MBuf) -> Self {
+        let mut chain = MChain::new();
+        chain.push_back(seg);
+        chain
+    }
+}
+
+impl FromIterator<MBuf> for MChain {
+    fn from_iter<I: IntoIterator<Item = MBuf>>(iter: I) -> Self {
+        let mut chain = MChain::new();
+        chain.extend(iter);
+        chain
+    }
+}
+
+impl Extend<MBuf> for MChain {
+    fn extend<I: IntoIterator<Item = MBuf>>(&mut self, iter: I) {
+        for seg in iter {
+            self.push_back(seg);
+        }
+    }
+}
+
+impl IntoIterator for MChain {
+    type Item = MBuf;
+    type IntoIter = vec_deque::IntoIter<MBuf>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.segs.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a MChain {
+    type Item = &'a MBuf;
+    type IntoIter = Segments<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.segments()
+    }
+}
+
+/// MChain分段迭代器
+pub struct Segments<'a> {
+    inner: vec_deque::Iter<'a, MBuf>,
+}
+
+impl<'a> Iterator for Segments<'a> {
+    type Item = &'a MBuf;
+
+    fn next(&mut self) -> Option<&'a MBuf> {
+        self.inner.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl DoubleEndedIterator for Segments<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back()
+    }
+}
+
+impl ExactSizeIterator for Segments<'_> {}
+
+/// 用于跨分段遍历MChain数据的游标结构
+///
+/// 数据位于同一分段时直接返回借用的切片，跨越分段边界时才复制到调用方提供的缓冲区
+pub struct ChainCursor<'a> {
+    chain: &'a MChain,
+    seg: usize,
+    seg_pos: usize,
+    pos: usize,
+}
+
+impl<'a> ChainCursor<'a> {
+    /// 创建一个新的游标
+    pub fn new(chain: &'a MChain) -> Self {
+        let mut cursor = Self { chain, seg: 0, seg_pos: 0, pos: 0 };
+        cursor.skip_exhausted();
+        cursor
+    }
+
+    /// 获取当前位置
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    /// 获取剩余未读取的字节数
+    pub fn remaining(&self) -> usize {
+        self.chain.len() - self.pos
+    }
+
+    /// 获取下一个字节，如果到达末尾返回None
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<u8> {
+        let byte = *self.current()?.first()?;
+        self.advance(1);
+        Some(byte)
+    }
+
+    /// 获取下一个长度为size的切片，仅当数据位于同一分段时返回，否则返回None且不移动游标
+    pub fn next_contiguous(&mut self, size: usize) -> Option<&'a [u8]> {
+        let current = self.current().unwrap_or(&[]);
+        if size > current.len() {
+            return None;
+        }
+        self.advance(size);
+        Some(&current[..size])
+    }
+
+    /// 获取下一个长度为size的切片，跨越分段时复制到scratch中；
+    /// 剩余数据不足或scratch不够大时返回None且不移动游标
+    pub fn next_slice<'b>(&mut self, size: usize, scratch: &'b mut [u8]) -> Option<&'b [u8]>
+    where
+        'a: 'b,
+    {
+        if size > self.remaining() {
+            return None;
+        }
+        if let Some(slice) = self.next_contiguous(size) {
+            return Some(slice);
+        }
+        let scratch = scratch.get_mut(..size)?;
+        self.copy_to(scratch);
+        Some(scratch)
+    }
+
+    /// 读取N个字节到定长数组，如果剩余数据不足返回None
+    pub fn read_array<const N: usize>(&mut self) -> Option<[u8; N]> {
+        if N > self.remaining() {
+            return None;
+        }
+        let mut out = [0u8; N];
+        self.copy_to(&mut out);
+        Some(out)
+    }
+
+    impl_typed_reads! {
+        read_u16_be, read_u16_le => u16;
+        read_u32_be, read_u32_le => u32;
+        read_u64_be, read_u64_le => u64;
+        read_i16_be, read_i16_le => i16;
+        read_i32_be, read_i32_le => i32;
+        read_i64_be, read_i64_le => i64;
+        read_f32_be, read_f32_le => f32;
+        read_f64_be, read_f64_le => f64;
+    }
+
+    /// 重置游标位置
+    pub fn reset(&mut self) {
+        self.seg = 0;
+        self.seg_pos = 0;
+        self.pos = 0;
+        self.skip_exhausted();
+    }
+
+    /// 当前分段中剩余的数据
+    fn current(&self) -> Option<&'a [u8]> {
+        let chain: &'a MChain = self.chain;
+        chain.segment(self.seg).map(|seg| &seg[self.seg_pos..])
+    }
+
+    /// 复制dst.len()个字节并前移游标，调用方保证剩余数据足够
+    fn copy_to(&mut self, dst: &mut [u8]) {
+        let mut filled = 0;
+        while filled < dst.len() {
+            let current = self.current().unwrap_or(&[]);
+            let n = current.len().min(dst.len() - filled);
+            dst[filled..filled + n].copy_from_slice(&current[..n]);
+            filled += n;
+            self.advance(n);
+        }
+    }
+
+    /// 在当前分段内前移游标
+    fn advance(&mut self, n: usize) {
+        self.seg_pos += n;
+        self.pos += n;
+        self.skip_exhausted();
+    }
+
+    /// 跳过已读完的分段和空分段
+    fn skip_exhausted(&mut self) {
+        while let Some(seg) = self.chain.segment(self.seg) {
+            if self.seg_pos < seg.len() {
+                break;
+            }
+            self.seg += 1;
+            self.seg_pos = 0;
+        }
+    }
+}
+
+impl Read for ChainCursor<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = buf.len().min(self.remaining());
+        self.copy_to(&mut buf[..n]);
+        Ok(n)
+    }
+}
+
+/// 把每部分复制到独立的分段中组成链，供各模块的测试构造分段边界
+#[cfg(test)]
+pub(crate) fn chain_of(parts: &[&[u8]]) -> MChain {
+    parts
+        .iter()
+        .map(|part| {
+            let mut seg = MBuf::with_capacity(part.len());
+            seg.append(part);
+            seg
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MPool;
+
+    #[test]
+    fn test_chain_compose() {
+        let pool = MPool::new(1, 64);
+        let mut header = pool.alloc();
+        header.append(b"HDR:");
+
+        let mut payload = MBuf::with_capacity(16);
+        payload.append(b"payload");
+        let shared = payload.clone();
+
+        let mut chain = MChain::new();
+        chain.push_back(shared);
+        chain.push_front(header);
+        assert_eq!(chain.len(), 11);
+        assert_eq!(chain.segment_count(), 2);
+        assert_eq!(chain.to_vec(), b"HDR:payload");
+        assert_eq!(chain.get(4), Some(b'p'));
+        assert_eq!(chain.get(11), None);
+
+        // 负载没有被复制
+        assert!(!payload.is_unique());
+        assert_eq!(chain.back().unwrap().as_ptr(), payload.as_ptr());
+
+        let lens: Vec<usize> = chain.segments().map(|seg| seg.len()).collect();
+        assert_eq!(lens, vec![4, 7]);
+
+        pool.free(chain.pop_front().unwrap());
+        assert_eq!(chain.len(), 7);
+    }
+
+    #[test]
+    fn test_chain_append() {
+        let mut a: MChain = (0..3)
+            .map(|i| {
+                let mut seg = MBuf::with_capacity(4);
+                seg.append(&[i; 2]);
+                seg
+            })
+            .collect();
+        let mut b = MChain::from(MBuf::with_capacity(4));
+        b.extend(a.clone());
+        a.append(&mut b);
+        assert_eq!(a.len(), 12);
+        assert_eq!(a.segment_count(), 7);
+        assert!(b.is_empty());
+        assert_eq!(a.bytes().filter(|&byte| byte == 2).count(), 4);
+    }
+
+    #[test]
+    fn test_chain_cursor() {
+        let chain = chain_of(&[b"\x12", b"", b"\x34\x00\x00", b"\x00\x01abc", b"def"]);
+        let mut cursor = chain.cursor();
+        assert_eq!(cursor.read_u16_be(), Some(0x1234));
+        assert_eq!(cursor.read_u32_be(), Some(1));
+        assert_eq!(cursor.position(), 6);
+
+        // 同一分段内直接借用
+        let slice = cursor.next_contiguous(2).unwrap();
+        assert_eq!(slice, b"ab");
+        assert!(std::ptr::eq(slice.as_ptr(), chain.segment(3).unwrap()[2..].as_ptr()));
+
+        // 跨分段时复制到scratch
+        assert_eq!(cursor.next_contiguous(3), None);
+        let mut scratch = [0u8; 8];
+        assert_eq!(cursor.next_slice(3, &mut scratch), Some(b"cde".as_ref()));
+        assert_eq!(cursor.next_slice(2, &mut scratch), None);
+        assert_eq!(cursor.next(), Some(b'f'));
+        assert_eq!(cursor.next(), None);
+        assert_eq!(cursor.remaining(), 0);
+
+        cursor.reset();
+        let mut small = [0u8; 1];
+        assert_eq!(cursor.next_slice(2, &mut small), None);
+        assert_eq!(cursor.position(), 0);
+        assert_eq!(cursor.next(), Some(0x12));
+
+        let mut rest = [0u8; 16];
+        assert_eq!(cursor.read(&mut rest).unwrap(), 11);
+        assert_eq!(&rest[..11], b"\x34\x00\x00\x00\x01abcdef");
+    }
+
+    #[test]
+    fn test_pullup() {
+        let payload = chain_of(&[b"ab", b"cdef"]).pop_back().unwrap();
+        let mut chain = chain_of(&[b"01", b"23", b"4"]);
+        chain.push_back(payload.clone());
+
+        assert!(chain.pullup(2));
+        assert_eq!(chain.segment_count(), 4);
+        assert!(chain.pullup(6));
+        assert_eq!(&chain.front().unwrap()[..], b"01234c");
+        assert_eq!(chain.segment_count(), 2);
+        assert_eq!(chain.to_vec(), b"01234cdef");
+
+        // 共享的分段只移动了视图，数据未被修改
+        assert_eq!(&payload[..], b"cdef");
+        assert!(!chain.pullup(10));
+        assert_eq!(chain.len(), 9);
+    }
+
+    #[test]
+    fn test_copy_data_and_back() {
+        let shared = chain_of(&[b"cdef"]).pop_back().unwrap();
+        let mut chain = chain_of(&[b"ab"]);
+        chain.push_back(shared.clone());
+
+        let mut out = [0u8; 3];
+        assert!(chain.copy_data(1, 3, &mut out));
+        assert_eq!(&out, b"bcd");
+        assert!(!chain.copy_data(4, 3, &mut out));
+
+        // 覆盖共享分段时写时复制
+        chain.copy_back(1, b"XYZ");
+        assert_eq!(chain.to_vec(), b"aXYZef");
+        assert_eq!(&shared[..], b"cdef");
+
+        // 超出链尾时扩展，中间补0
+        chain.copy_back(8, b"!!");
+        assert_eq!(chain.len(), 10);
+        assert_eq!(chain.to_vec(), b"aXYZef\0\0!!");
+    }
+
+    #[test]
+    fn test_slice_shares_segments() {
+        let chain = chain_of(&[b"abc", b"defg", b"hi"]);
+        let range = chain.slice(2..8);
+        assert_eq!(range.to_vec(), b"cdefgh");
+        assert_eq!(range.segment_count(), 3);
+        assert_eq!(range.front().unwrap().as_ptr(), chain.front().unwrap()[2..].as_ptr());
+        assert!(!chain.front().unwrap().is_unique());
+
+        assert_eq!(chain.slice(3..7).segment_count(), 1);
+        assert!(chain.slice(4..4).is_empty());
+    }
+
+    #[test]
+    fn test_defrag_and_linearize() {
+        let pool = MPool::new(0, 4);
+        let mut chain = chain_of(&[b"a", b"bc", b"d", b"efghij", b"k", b"lmnop"]);
+        chain.defrag(&pool);
+        let lens: Vec<usize> = chain.segments().map(|seg| seg.len()).collect();
+        assert_eq!(lens, vec![4, 6, 1, 5]);
+        assert_eq!(chain.to_vec(), b"abcdefghijklmnop");
+
+        assert_eq!(chain.linearize(), b"abcdefghijklmnop");
+        assert_eq!(chain.segment_count(), 1);
+    }
+
+    /// 每次最多写入limit字节的写入器，用于模拟部分写入
+    struct Trickle {
+        out: Vec<u8>,
+        limit: usize,
+        calls: usize,
+    }
+
+    impl Write for Trickle {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.write_vectored(&[IoSlice::new(buf)])
+        }
+
+        fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+            self.calls += 1;
+            let mut written = 0;
+            for buf in bufs {
+                let n = buf.len().min(self.limit - written);
+                self.out.extend_from_slice(&buf[..n]);
+                written += n;
+            }
+            Ok(written)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_io_slices_coalesce() {
+        let mut buf = MBuf::with_capacity(16);
+        buf.append(b"0123456789");
+        let mut chain = MChain::new();
+        chain.push_back(buf.slice(..3));
+        chain.push_back(buf.slice(3..7));
+        chain.push_back(MBuf::with_capacity(0));
+        chain.push_back(buf.slice(7..));
+        chain.push_back(buf.slice(2..4));
+
+        let mut iov = [IoSlice::new(&[]); 4];
+        assert_eq!(chain.as_io_slices(&mut iov), 2);
+        assert_eq!(&*iov[0], b"0123456789");
+        assert_eq!(&*iov[1], b"23");
+
+        let mut one = [IoSlice::new(&[]); 1];
+        assert_eq!(chain.as_io_slices(&mut one), 1);
+    }
+
+    #[test]
+    fn test_write_to_partial() {
+        let mut chain = chain_of(&[b"abc", b"defgh", b"ij"]);
+        let mut w = Trickle { out: Vec::new(), limit: 4, calls: 0 };
+        assert_eq!(chain.write_to(&mut w).unwrap(), 10);
+        assert_eq!(w.out, b"abcdefghij");
+        assert_eq!(w.calls, 3);
+        assert!(chain.is_empty());
+        assert_eq!(chain.segment_count(), 0);
+    }
+
+    #[test]
+    fn test_read_vectored_from() {
+        let pool = MPool::new(4, 4);
+        let mut chain = MChain::new();
+        let mut src = &b"scattered!"[..];
+        assert_eq!(chain.read_vectored_from(&mut src, &pool, 4).unwrap(), 10);
+        assert_eq!(chain.segment_count(), 3);
+        assert_eq!(chain.to_vec(), b"scattered!");
+        assert_eq!(pool.free_count(), 1);
+    }
+}
@@ -0,0 +1,275 @@
+// MIT License
+//
+// Copyright (c) 2023 gaosg
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::io::{self, IoSlice};
+use std::mem;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+use std::os::fd::AsRawFd;
+use std::ptr;
+use super::{MBuf, MChain, MPool, IOV_MAX};
+
+/// 可以作为一个数据报发送的缓冲区
+pub trait Datagram {
+    /// 将数据报内容追加为IoSlice，数据超出IOV_MAX个分段时返回错误
+    fn io_slices<'a>(&'a self, dst: &mut Vec<IoSlice<'a>>) -> io::Result<()>;
+}
+
+impl Datagram for MBuf {
+    fn io_slices<'a>(&'a self, dst: &mut Vec<IoSlice<'a>>) -> io::Result<()> {
+        dst.push(IoSlice::new(self));
+        Ok(())
+    }
+}
+
+impl Datagram for MChain {
+    fn io_slices<'a>(&'a self, dst: &mut Vec<IoSlice<'a>>) -> io::Result<()> {
+        let start = dst.len();
+        dst.resize(start + self.segment_count().min(IOV_MAX), IoSlice::new(&[]));
+        let count = self.as_io_slices(&mut dst[start..]);
+        dst.truncate(start + count);
+        if dst[start..].iter().map(|iov| iov.len()).sum::<usize>() != self.len() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "datagram exceeds IOV_MAX segments"));
+        }
+        Ok(())
+    }
+}
+
+impl<T: Datagram + ?Sized> Datagram for &T {
+    fn io_slices<'a>(&'a self, dst: &mut Vec<IoSlice<'a>>) -> io::Result<()> {
+        (**self).io_slices(dst)
+    }
+}
+
+/// 使用recvmmsg一次接收最多max个数据报到从池中分配的MBuf中，追加到out，返回接收的数量
+///
+/// 至少收到一个数据报后即返回（MSG_WAITFORONE），数据长度和源地址写入每个MBuf及其元数据，
+/// 超出池容量的数据报被截断并在元数据中标记`truncated`，未用到的缓冲区归还池中
+#[cfg_attr(feature = "tracking", track_caller)]
+pub fn recv_batch(socket: &impl AsRawFd, pool: &MPool, max: usize, out: &mut Vec<MBuf>) -> io::Result<usize> {
+    let max = max.max(1);
+    let mut bufs = Vec::with_capacity(max);
+    for _ in 0..max {
+        bufs.push(pool.alloc());
+    }
+    let mut iovs: Vec<libc::iovec> = bufs
+        .iter_mut()
+        .map(|buf| {
+            let spare = buf.spare_capacity_mut();
+            libc::iovec { iov_base: spare.as_mut_ptr().cast(), iov_len: spare.len() }
+        })
+        .collect();
+    let mut addrs: Vec<libc::sockaddr_storage> = vec![unsafe { mem::zeroed() }; max];
+    let mut msgs: Vec<libc::mmsghdr> = iovs
+        .iter_mut()
+        .zip(addrs.iter_mut())
+        .map(|(iov, addr)| {
+            let mut msg: libc::mmsghdr = unsafe { mem::zeroed() };
+            msg.msg_hdr.msg_name = (addr as *mut libc::sockaddr_storage).cast();
+            msg.msg_hdr.msg_namelen = mem::size_of::<libc::sockaddr_storage>() as _;
+            msg.msg_hdr.msg_iov = iov;
+            msg.msg_hdr.msg_iovlen = 1;
+            msg
+        })
+        .collect();
+
+    let received = unsafe {
+        libc::recvmmsg(
+            socket.as_raw_fd(),
+            msgs.as_mut_ptr(),
+            max as _,
+            libc::MSG_WAITFORONE as _,
+            ptr::null_mut(),
+        )
+    };
+    let received = if received < 0 { Err(io::Error::last_os_error()) } else { Ok(received as usize) };
+
+    let count = *received.as_ref().unwrap_or(&0);
+    for (i, mut buf) in bufs.into_iter().enumerate() {
+        if i >= count {
+            pool.free(buf);
+            continue;
+        }
+        let msg = &msgs[i];
+        buf.commit((msg.msg_len as usize).min(buf.tailroom()));
+        buf.meta_mut().src_addr = to_socket_addr(&addrs[i], msg.msg_hdr.msg_namelen);
+        buf.meta_mut().truncated = msg.msg_hdr.msg_flags & libc::MSG_TRUNC != 0;
+        out.push(buf);
+    }
+    received
+}
+
+/// 使用sendmmsg一次发送一批数据报，目的地址为None时使用已连接套接字的对端地址，
+/// 返回实际发送的数据报数量
+pub fn send_batch<D: Datagram>(socket: &impl AsRawFd, packets: &[(D, Option<SocketAddr>)]) -> io::Result<usize> {
+    if packets.is_empty() {
+        return Ok(0);
+    }
+
+    let mut iovs = Vec::with_capacity(packets.len());
+    let mut ranges = Vec::with_capacity(packets.len());
+    for (packet, _) in packets {
+        let start = iovs.len();
+        packet.io_slices(&mut iovs)?;
+        ranges.push(start..iovs.len());
+    }
+    let mut addrs: Vec<(libc::sockaddr_storage, libc::socklen_t)> = packets
+        .iter()
+        .map(|(_, dst)| dst.as_ref().map_or((unsafe { mem::zeroed() }, 0), from_socket_addr))
+        .collect();
+
+    let mut msgs: Vec<libc::mmsghdr> = ranges
+        .iter()
+        .zip(addrs.iter_mut())
+        .map(|(range, (addr, addr_len))| {
+            let mut msg: libc::mmsghdr = unsafe { mem::zeroed() };
+            if *addr_len > 0 {
+                msg.msg_hdr.msg_name = (addr as *mut libc::sockaddr_storage).cast();
+                msg.msg_hdr.msg_namelen = *addr_len;
+            }
+            // IoSlice在Unix上与iovec的内存布局一致
+            msg.msg_hdr.msg_iov = iovs[range.clone()].as_ptr() as *mut libc::iovec;
+            msg.msg_hdr.msg_iovlen = range.len() as _;
+            msg
+        })
+        .collect();
+
+    let sent = unsafe { libc::sendmmsg(socket.as_raw_fd(), msgs.as_mut_ptr(), msgs.len() as _, 0) };
+    if sent < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(sent as usize)
+}
+
+/// 将内核填写的地址转换为SocketAddr
+fn to_socket_addr(addr: &libc::sockaddr_storage, len: libc::socklen_t) -> Option<SocketAddr> {
+    match addr.ss_family as libc::c_int {
+        libc::AF_INET if len as usize >= mem::size_of::<libc::sockaddr_in>() => {
+            let addr = unsafe { &*(addr as *const libc::sockaddr_storage).cast::<libc::sockaddr_in>() };
+            let ip = Ipv4Addr::from(u32::from_be(addr.sin_addr.s_addr));
+            Some(SocketAddr::V4(SocketAddrV4::new(ip, u16::from_be(addr.sin_port))))
+        }
+        libc::AF_INET6 if len as usize >= mem::size_of::<libc::sockaddr_in6>() => {
+            let addr = unsafe { &*(addr as *const libc::sockaddr_storage).cast::<libc::sockaddr_in6>() };
+            let ip = Ipv6Addr::from(addr.sin6_addr.s6_addr);
+            Some(SocketAddr::V6(SocketAddrV6::new(
+                ip,
+                u16::from_be(addr.sin6_port),
+                addr.sin6_flowinfo,
+                addr.sin6_scope_id,
+            )))
+        }
+        _ => None,
+    }
+}
+
+/// 将SocketAddr转换为内核使用的地址结构
+fn from_socket_addr(addr: &SocketAddr) -> (libc::sockaddr_storage, libc::socklen_t) {
+    let mut storage: libc::sockaddr_storage = unsafe { mem::zeroed() };
+    let len = match addr {
+        SocketAddr::V4(addr) => {
+            let raw = unsafe { &mut *(&mut storage as *mut libc::sockaddr_storage).cast::<libc::sockaddr_in>() };
+            raw.sin_family = libc::AF_INET as _;
+            raw.sin_port = addr.port().to_be();
+            raw.sin_addr.s_addr = u32::from(*addr.ip()).to_be();
+            mem::size_of::<libc::sockaddr_in>()
+        }
+        SocketAddr::V6(addr) => {
+            let raw = unsafe { &mut *(&mut storage as *mut libc::sockaddr_storage).cast::<libc::sockaddr_in6>() };
+            raw.sin6_family = libc::AF_INET6 as _;
+            raw.sin6_port = addr.port().to_be();
+            raw.sin6_addr.s6_addr = addr.ip().octets();
+            raw.sin6_flowinfo = addr.flowinfo();
+            raw.sin6_scope_id = addr.scope_id();
+            mem::size_of::<libc::sockaddr_in6>()
+        }
+    };
+    (storage, len as libc::socklen_t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::UdpSocket;
+
+    #[test]
+    fn test_loopback_batch() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let dst = Some(receiver.local_addr().unwrap());
+
+        let mut single = MBuf::with_capacity(8);
+        single.append(b"first");
+        let mut chain = MChain::from(single.slice(..2));
+        chain.extend_from_slice(b"-chain");
+        let sent = send_batch(&sender, &[(&chain, dst), (&chain, dst)]).unwrap();
+        assert_eq!(sent, 2);
+        assert_eq!(send_batch(&sender, &[(single, dst)]).unwrap(), 1);
+
+        let pool = MPool::new(4, 64);
+        let mut out = Vec::new();
+        let mut received = 0;
+        while received < 3 {
+            received += recv_batch(&receiver, &pool, 4, &mut out).unwrap();
+        }
+        assert_eq!(out.len(), 3);
+        assert_eq!(&out[0][..], b"fi-chain");
+        assert_eq!(&out[2][..], b"first");
+        assert_eq!(out[0].meta().src_addr, Some(sender.local_addr().unwrap()));
+        for buf in out {
+            pool.free(buf);
+        }
+        assert_eq!(pool.allocated_count(), 0);
+    }
+
+    #[test]
+    fn test_recycled_buffer_and_truncation() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+        sender.connect(receiver.local_addr().unwrap()).unwrap();
+        let pool = MPool::new(1, 8);
+
+        // 同一个缓冲区接收两次，第二次不会接在上一个报文之后
+        for payload in [&b"one"[..], b"two!"] {
+            sender.send(payload).unwrap();
+            let mut out = Vec::new();
+            assert_eq!(recv_batch(&receiver, &pool, 1, &mut out).unwrap(), 1);
+            assert_eq!(&out[0][..], payload);
+            assert!(!out[0].meta().truncated);
+            pool.free(out.pop().unwrap());
+        }
+
+        sender.send(b"longer than eight").unwrap();
+        let mut out = Vec::new();
+        recv_batch(&receiver, &pool, 1, &mut out).unwrap();
+        assert_eq!(&out[0][..], b"longer t");
+        assert!(out[0].meta().truncated);
+    }
+
+    #[test]
+    fn test_socket_addr_roundtrip() {
+        for addr in ["10.1.2.3:53", "[fe80::1]:4433"] {
+            let addr: SocketAddr = addr.parse().unwrap();
+            let (raw, len) = from_socket_addr(&addr);
+            assert_eq!(to_socket_addr(&raw, len), Some(addr));
+        }
+    }
+}
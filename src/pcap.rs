@@ -0,0 +1,671 @@
+// MIT License
+//
+// Copyright (c) 2023 gaosg
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! pcap与pcapng抓包文件的读写，读取的报文从MPool分配并在元数据中携带时间戳
+
+use std::io::{self, Read, Write};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use super::{MBuf, MChain, MPool, Meta};
+use super::mbuf_pool::AllocSite;
+
+/// 以太网链路类型
+pub const LINKTYPE_ETHERNET: u32 = 1;
+/// 裸IP链路类型
+pub const LINKTYPE_RAW: u32 = 101;
+
+/// 单个报文允许的最大捕获长度，超过时视为文件损坏
+const MAX_PACKET_LEN: usize = 64 * 1024 * 1024;
+
+const PCAP_MAGIC_MICROS: u32 = 0xa1b2_c3d4;
+const PCAP_MAGIC_NANOS: u32 = 0xa1b2_3c4d;
+const PCAP_SNAPLEN: u32 = 262_144;
+
+const BLOCK_SHB: u32 = 0x0a0d_0d0a;
+const BLOCK_IDB: u32 = 0x0000_0001;
+const BLOCK_SPB: u32 = 0x0000_0003;
+const BLOCK_EPB: u32 = 0x0000_0006;
+const BYTE_ORDER_MAGIC: u32 = 0x1a2b_3c4d;
+const OPT_END: u16 = 0;
+const OPT_IF_TSRESOL: u16 = 9;
+
+/// 文件使用的字节序
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Endian {
+    Little,
+    Big,
+}
+
+impl Endian {
+    fn u16(self, bytes: &[u8]) -> u16 {
+        let bytes = [bytes[0], bytes[1]];
+        match self {
+            Endian::Little => u16::from_le_bytes(bytes),
+            Endian::Big => u16::from_be_bytes(bytes),
+        }
+    }
+
+    fn u32(self, bytes: &[u8]) -> u32 {
+        let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+        match self {
+            Endian::Little => u32::from_le_bytes(bytes),
+            Endian::Big => u32::from_be_bytes(bytes),
+        }
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// 读满buf，在读取任何数据之前遇到文件结尾时返回false
+fn read_exact_or_eof<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<bool> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) if filled == 0 => return Ok(false),
+            Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(true)
+}
+
+/// 丢弃n个字节
+fn skip<R: Read>(reader: &mut R, n: usize) -> io::Result<()> {
+    let copied = io::copy(&mut reader.by_ref().take(n as u64), &mut io::sink())?;
+    if copied < n as u64 {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(())
+}
+
+/// 从池中分配缓冲区并读入len字节的报文
+fn read_packet<R: Read>(reader: &mut R, pool: &MPool, site: AllocSite, len: usize, timestamp: Option<Duration>) -> io::Result<MBuf> {
+    if len > MAX_PACKET_LEN {
+        return Err(invalid("packet length exceeds limit"));
+    }
+    let mut buf = pool.alloc_at(site);
+    buf.reserve(len);
+    if let Err(e) = reader.read_exact(&mut buf.spare_capacity_mut()[..len]) {
+        pool.free(buf);
+        return Err(e);
+    }
+    buf.commit(len);
+    buf.meta_mut().timestamp = timestamp;
+    Ok(buf)
+}
+
+/// 报文的时间戳，元数据中没有时使用当前时间
+fn packet_timestamp(meta: &Meta) -> Duration {
+    meta.timestamp
+        .unwrap_or_else(|| SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default())
+}
+
+/// 将按units_per_sec计数的时间戳转换为Duration
+fn ticks_to_duration(ticks: u64, units_per_sec: u64) -> Duration {
+    let secs = ticks / units_per_sec;
+    let nanos = (ticks % units_per_sec) as u128 * 1_000_000_000 / units_per_sec as u128;
+    Duration::new(secs, nanos as u32)
+}
+
+/// 经典pcap文件读取器，支持两种字节序及微秒/纳秒时间戳
+pub struct PcapReader<'p, R> {
+    reader: R,
+    pool: &'p MPool,
+    site: AllocSite,
+    endian: Endian,
+    nanos: bool,
+    linktype: u32,
+    snaplen: u32,
+}
+
+impl<'p, R: Read> PcapReader<'p, R> {
+    /// 读取文件头，报文从pool中分配
+    ///
+    /// 启用`tracking`特性时，读出的报文记在调用`new`的位置
+    #[cfg_attr(feature = "tracking", track_caller)]
+    pub fn new(mut reader: R, pool: &'p MPool) -> io::Result<Self> {
+        let mut header = [0u8; 24];
+        reader.read_exact(&mut header)?;
+        let magic = u32::from_le_bytes(header[..4].try_into().unwrap());
+        let (endian, nanos) = match magic {
+            PCAP_MAGIC_MICROS => (Endian::Little, false),
+            PCAP_MAGIC_NANOS => (Endian::Little, true),
+            _ => match magic.swap_bytes() {
+                PCAP_MAGIC_MICROS => (Endian::Big, false),
+                PCAP_MAGIC_NANOS => (Endian::Big, true),
+                _ => return Err(invalid("not a pcap file")),
+            },
+        };
+        Ok(Self {
+            reader,
+            pool,
+            site: AllocSite::caller(),
+            endian,
+            nanos,
+            snaplen: endian.u32(&header[16..20]),
+            linktype: endian.u32(&header[20..24]),
+        })
+    }
+
+    /// 链路类型
+    pub fn linktype(&self) -> u32 {
+        self.linktype
+    }
+
+    /// 最大捕获长度
+    pub fn snaplen(&self) -> u32 {
+        self.snaplen
+    }
+
+    /// 时间戳是否为纳秒精度
+    pub fn is_nanos(&self) -> bool {
+        self.nanos
+    }
+
+    /// 读取下一个报文，到达文件结尾时返回None
+    pub fn next_packet(&mut self) -> io::Result<Option<MBuf>> {
+        let mut record = [0u8; 16];
+        if !read_exact_or_eof(&mut self.reader, &mut record)? {
+            return Ok(None);
+        }
+        let secs = self.endian.u32(&record[0..4]) as u64;
+        let frac = self.endian.u32(&record[4..8]);
+        let captured = self.endian.u32(&record[8..12]) as usize;
+        let nanos = if self.nanos { frac } else { frac.saturating_mul(1000) };
+        if nanos >= 1_000_000_000 {
+            return Err(invalid("invalid timestamp fraction"));
+        }
+        let timestamp = Duration::new(secs, nanos);
+        read_packet(&mut self.reader, self.pool, self.site, captured, Some(timestamp)).map(Some)
+    }
+}
+
+impl<R: Read> Iterator for PcapReader<'_, R> {
+    type Item = io::Result<MBuf>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_packet().transpose()
+    }
+}
+
+/// 经典pcap文件写入器，以小端序写出
+pub struct PcapWriter<W> {
+    writer: W,
+    nanos: bool,
+}
+
+impl<W: Write> PcapWriter<W> {
+    /// 写入微秒精度的文件头
+    pub fn new(writer: W, linktype: u32) -> io::Result<Self> {
+        Self::create(writer, linktype, false)
+    }
+
+    /// 写入纳秒精度的文件头
+    pub fn with_nanos(writer: W, linktype: u32) -> io::Result<Self> {
+        Self::create(writer, linktype, true)
+    }
+
+    fn create(mut writer: W, linktype: u32, nanos: bool) -> io::Result<Self> {
+        let magic = if nanos { PCAP_MAGIC_NANOS } else { PCAP_MAGIC_MICROS };
+        let mut header = Vec::with_capacity(24);
+        header.extend_from_slice(&magic.to_le_bytes());
+        header.extend_from_slice(&2u16.to_le_bytes());
+        header.extend_from_slice(&4u16.to_le_bytes());
+        header.extend_from_slice(&0i32.to_le_bytes());
+        header.extend_from_slice(&0u32.to_le_bytes());
+        header.extend_from_slice(&PCAP_SNAPLEN.to_le_bytes());
+        header.extend_from_slice(&linktype.to_le_bytes());
+        writer.write_all(&header)?;
+        Ok(Self { writer, nanos })
+    }
+
+    /// 写入一个报文，时间戳取自元数据
+    pub fn write_packet(&mut self, buf: &MBuf) -> io::Result<()> {
+        self.write_record(packet_timestamp(buf.meta()), buf.len())?;
+        self.writer.write_all(buf)
+    }
+
+    /// 将整条链作为一个报文写入，时间戳取自第一个分段的元数据
+    pub fn write_chain(&mut self, chain: &MChain) -> io::Result<()> {
+        let meta = chain.front().map(|seg| *seg.meta()).unwrap_or_default();
+        self.write_record(packet_timestamp(&meta), chain.len())?;
+        for seg in chain {
+            self.writer.write_all(seg)?;
+        }
+        Ok(())
+    }
+
+    /// 刷新底层写入器
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    /// 取回底层写入器
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn write_record(&mut self, timestamp: Duration, len: usize) -> io::Result<()> {
+        let frac = if self.nanos { timestamp.subsec_nanos() } else { timestamp.subsec_micros() };
+        let mut record = [0u8; 16];
+        record[0..4].copy_from_slice(&(timestamp.as_secs() as u32).to_le_bytes());
+        record[4..8].copy_from_slice(&frac.to_le_bytes());
+        record[8..12].copy_from_slice(&(len as u32).to_le_bytes());
+        record[12..16].copy_from_slice(&(len as u32).to_le_bytes());
+        self.writer.write_all(&record)
+    }
+}
+
+/// pcapng接口描述
+#[derive(Debug, Clone, Copy)]
+struct Interface {
+    linktype: u32,
+    units_per_sec: u64,
+}
+
+/// pcapng文件读取器，解析SHB/IDB/EPB/SPB块，跳过其他块
+pub struct PcapNgReader<'p, R> {
+    reader: R,
+    pool: &'p MPool,
+    site: AllocSite,
+    endian: Endian,
+    interfaces: Vec<Interface>,
+}
+
+impl<'p, R: Read> PcapNgReader<'p, R> {
+    /// 读取第一个节头块，报文从pool中分配
+    ///
+    /// 启用`tracking`特性时，读出的报文记在调用`new`的位置
+    #[cfg_attr(feature = "tracking", track_caller)]
+    pub fn new(mut reader: R, pool: &'p MPool) -> io::Result<Self> {
+        let mut head = [0u8; 8];
+        reader.read_exact(&mut head)?;
+        if u32::from_le_bytes(head[..4].try_into().unwrap()) != BLOCK_SHB {
+            return Err(invalid("not a pcapng file"));
+        }
+        let mut pcapng = Self {
+            reader,
+            pool,
+            site: AllocSite::caller(),
+            endian: Endian::Little,
+            interfaces: Vec::new(),
+        };
+        pcapng.read_section_header(&head[4..8])?;
+        Ok(pcapng)
+    }
+
+    /// 当前节中接口的链路类型
+    pub fn linktype(&self, interface_id: usize) -> Option<u32> {
+        self.interfaces.get(interface_id).map(|interface| interface.linktype)
+    }
+
+    /// 读取下一个报文，到达文件结尾时返回None
+    pub fn next_packet(&mut self) -> io::Result<Option<MBuf>> {
+        loop {
+            let mut head = [0u8; 8];
+            if !read_exact_or_eof(&mut self.reader, &mut head)? {
+                return Ok(None);
+            }
+            let block_type = self.endian.u32(&head[0..4]);
+            if block_type == BLOCK_SHB {
+                self.read_section_header(&head[4..8])?;
+                continue;
+            }
+
+            let total = self.endian.u32(&head[4..8]) as usize;
+            if total < 12 || total & 3 != 0 {
+                return Err(invalid("invalid block length"));
+            }
+            let body = total - 12;
+            match block_type {
+                BLOCK_EPB => return self.read_enhanced_packet(body).map(Some),
+                BLOCK_SPB => return self.read_simple_packet(body).map(Some),
+                BLOCK_IDB => {
+                    let data = self.read_body(body)?;
+                    self.add_interface(&data)?;
+                }
+                _ => skip(&mut self.reader, body + 4)?,
+            }
+        }
+    }
+
+    /// 解析节头块，len_bytes为块长度字段
+    fn read_section_header(&mut self, len_bytes: &[u8]) -> io::Result<()> {
+        let mut magic = [0u8; 4];
+        self.reader.read_exact(&mut magic)?;
+        self.endian = match u32::from_le_bytes(magic) {
+            BYTE_ORDER_MAGIC => Endian::Little,
+            magic if magic.swap_bytes() == BYTE_ORDER_MAGIC => Endian::Big,
+            _ => return Err(invalid("invalid byte-order magic")),
+        };
+        let total = self.endian.u32(len_bytes) as usize;
+        if total < 28 || total & 3 != 0 {
+            return Err(invalid("invalid section header length"));
+        }
+        self.interfaces.clear();
+        skip(&mut self.reader, total - 12)
+    }
+
+    /// 读取块体及结尾的长度字段，返回块体
+    fn read_body(&mut self, body: usize) -> io::Result<Vec<u8>> {
+        if body > MAX_PACKET_LEN {
+            return Err(invalid("block length exceeds limit"));
+        }
+        let mut data = vec![0u8; body + 4];
+        self.reader.read_exact(&mut data)?;
+        data.truncate(body);
+        Ok(data)
+    }
+
+    fn add_interface(&mut self, data: &[u8]) -> io::Result<()> {
+        if data.len() < 8 {
+            return Err(invalid("truncated interface description block"));
+        }
+        let mut interface = Interface {
+            linktype: self.endian.u16(&data[0..2]) as u32,
+            units_per_sec: 1_000_000,
+        };
+        let mut options = &data[8..];
+        while options.len() >= 4 {
+            let code = self.endian.u16(&options[0..2]);
+            let len = self.endian.u16(&options[2..4]) as usize;
+            let value = options.get(4..4 + len).ok_or_else(|| invalid("truncated option"))?;
+            match code {
+                OPT_END => break,
+                OPT_IF_TSRESOL if len == 1 => {
+                    let exp = (value[0] & 0x7f) as u32;
+                    let base: u64 = if value[0] & 0x80 == 0 { 10 } else { 2 };
+                    interface.units_per_sec =
+                        base.checked_pow(exp).ok_or_else(|| invalid("unsupported timestamp resolution"))?;
+                }
+                _ => {}
+            }
+            options = options.get(4 + len.next_multiple_of(4)..).unwrap_or(&[]);
+        }
+        self.interfaces.push(interface);
+        Ok(())
+    }
+
+    fn read_enhanced_packet(&mut self, body: usize) -> io::Result<MBuf> {
+        let mut fixed = [0u8; 20];
+        if body < fixed.len() {
+            return Err(invalid("truncated enhanced packet block"));
+        }
+        self.reader.read_exact(&mut fixed)?;
+        let interface_id = self.endian.u32(&fixed[0..4]) as usize;
+        let ticks = ((self.endian.u32(&fixed[4..8]) as u64) << 32) | self.endian.u32(&fixed[8..12]) as u64;
+        let captured = self.endian.u32(&fixed[12..16]) as usize;
+        if captured > body - fixed.len() {
+            return Err(invalid("captured length exceeds block"));
+        }
+        let interface = self
+            .interfaces
+            .get(interface_id)
+            .copied()
+            .ok_or_else(|| invalid("unknown interface id"))?;
+
+        let timestamp = ticks_to_duration(ticks, interface.units_per_sec);
+        let buf = read_packet(&mut self.reader, self.pool, self.site, captured, Some(timestamp))?;
+        if let Err(e) = skip(&mut self.reader, body - fixed.len() - captured + 4) {
+            self.pool.free(buf);
+            return Err(e);
+        }
+        Ok(buf)
+    }
+
+    fn read_simple_packet(&mut self, body: usize) -> io::Result<MBuf> {
+        let mut original = [0u8; 4];
+        if body < original.len() {
+            return Err(invalid("truncated simple packet block"));
+        }
+        self.reader.read_exact(&mut original)?;
+        let captured = (self.endian.u32(&original) as usize).min(body - original.len());
+        let buf = read_packet(&mut self.reader, self.pool, self.site, captured, None)?;
+        if let Err(e) = skip(&mut self.reader, body - original.len() - captured + 4) {
+            self.pool.free(buf);
+            return Err(e);
+        }
+        Ok(buf)
+    }
+}
+
+impl<R: Read> Iterator for PcapNgReader<'_, R> {
+    type Item = io::Result<MBuf>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_packet().transpose()
+    }
+}
+
+/// pcapng文件写入器，写出一个节头块和一个接口描述块，报文使用增强报文块
+pub struct PcapNgWriter<W> {
+    writer: W,
+    nanos: bool,
+}
+
+impl<W: Write> PcapNgWriter<W> {
+    /// 写入使用微秒时间戳的节头和接口描述
+    pub fn new(writer: W, linktype: u32) -> io::Result<Self> {
+        Self::create(writer, linktype, false)
+    }
+
+    /// 写入使用纳秒时间戳（if_tsresol = 9）的节头和接口描述
+    pub fn with_nanos(writer: W, linktype: u32) -> io::Result<Self> {
+        Self::create(writer, linktype, true)
+    }
+
+    fn create(mut writer: W, linktype: u32, nanos: bool) -> io::Result<Self> {
+        let mut shb = Vec::with_capacity(16);
+        shb.extend_from_slice(&BYTE_ORDER_MAGIC.to_le_bytes());
+        shb.extend_from_slice(&1u16.to_le_bytes());
+        shb.extend_from_slice(&0u16.to_le_bytes());
+        shb.extend_from_slice(&(-1i64).to_le_bytes());
+        write_block(&mut writer, BLOCK_SHB, &[&shb])?;
+
+        let mut idb = Vec::with_capacity(20);
+        idb.extend_from_slice(&(linktype as u16).to_le_bytes());
+        idb.extend_from_slice(&0u16.to_le_bytes());
+        idb.extend_from_slice(&0u32.to_le_bytes());
+        if nanos {
+            idb.extend_from_slice(&OPT_IF_TSRESOL.to_le_bytes());
+            idb.extend_from_slice(&1u16.to_le_bytes());
+            idb.extend_from_slice(&[9, 0, 0, 0]);
+            idb.extend_from_slice(&OPT_END.to_le_bytes());
+            idb.extend_from_slice(&0u16.to_le_bytes());
+        }
+        write_block(&mut writer, BLOCK_IDB, &[&idb])?;
+        Ok(Self { writer, nanos })
+    }
+
+    /// 写入一个报文，时间戳取自元数据
+    pub fn write_packet(&mut self, buf: &MBuf) -> io::Result<()> {
+        let fixed = self.packet_header(packet_timestamp(buf.meta()), buf.len());
+        write_block(&mut self.writer, BLOCK_EPB, &[&fixed, buf])
+    }
+
+    /// 将整条链作为一个报文写入，时间戳取自第一个分段的元数据
+    pub fn write_chain(&mut self, chain: &MChain) -> io::Result<()> {
+        let meta = chain.front().map(|seg| *seg.meta()).unwrap_or_default();
+        let fixed = self.packet_header(packet_timestamp(&meta), chain.len());
+        let mut parts: Vec<&[u8]> = vec![&fixed];
+        parts.extend(chain.segments().map(|seg| &seg[..]));
+        write_block(&mut self.writer, BLOCK_EPB, &parts)
+    }
+
+    /// 刷新底层写入器
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    /// 取回底层写入器
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn packet_header(&self, timestamp: Duration, len: usize) -> [u8; 20] {
+        let ticks = if self.nanos { timestamp.as_nanos() } else { timestamp.as_micros() } as u64;
+        let mut fixed = [0u8; 20];
+        fixed[4..8].copy_from_slice(&((ticks >> 32) as u32).to_le_bytes());
+        fixed[8..12].copy_from_slice(&(ticks as u32).to_le_bytes());
+        fixed[12..16].copy_from_slice(&(len as u32).to_le_bytes());
+        fixed[16..20].copy_from_slice(&(len as u32).to_le_bytes());
+        fixed
+    }
+}
+
+/// 写出一个块：类型、总长度、按4字节对齐的块体和结尾的总长度
+fn write_block<W: Write>(writer: &mut W, block_type: u32, parts: &[&[u8]]) -> io::Result<()> {
+    let body: usize = parts.iter().map(|part| part.len()).sum();
+    let padding = body.next_multiple_of(4) - body;
+    let total = ((12 + body + padding) as u32).to_le_bytes();
+    writer.write_all(&block_type.to_le_bytes())?;
+    writer.write_all(&total)?;
+    for part in parts {
+        writer.write_all(part)?;
+    }
+    writer.write_all(&[0u8; 3][..padding])?;
+    writer.write_all(&total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packet(data: &[u8], timestamp: Duration) -> MBuf {
+        let mut buf = MBuf::with_capacity(data.len());
+        buf.append(data);
+        buf.meta_mut().timestamp = Some(timestamp);
+        buf
+    }
+
+    #[test]
+    fn test_pcap_roundtrip() {
+        let pool = MPool::new(2, 16);
+        let ts = Duration::new(1_700_000_000, 123_456_789);
+        for nanos in [false, true] {
+            let mut writer = if nanos {
+                PcapWriter::with_nanos(Vec::new(), LINKTYPE_ETHERNET).unwrap()
+            } else {
+                PcapWriter::new(Vec::new(), LINKTYPE_ETHERNET).unwrap()
+            };
+            writer.write_packet(&packet(b"first packet", ts)).unwrap();
+            let mut chain = MChain::from(packet(b"head|", ts));
+            chain.extend_from_slice(&[0xaa; 40]);
+            writer.write_chain(&chain).unwrap();
+            let file = writer.into_inner();
+
+            let mut reader = PcapReader::new(&file[..], &pool).unwrap();
+            assert_eq!(reader.linktype(), LINKTYPE_ETHERNET);
+            assert_eq!(reader.is_nanos(), nanos);
+            let first = reader.next_packet().unwrap().unwrap();
+            assert_eq!(&first[..], b"first packet");
+            let expected = if nanos { ts } else { Duration::new(ts.as_secs(), 123_456_000) };
+            assert_eq!(first.meta().timestamp, Some(expected));
+            let second = reader.next_packet().unwrap().unwrap();
+            assert_eq!(second.len(), 45);
+            assert!(reader.next_packet().unwrap().is_none());
+            pool.free(first);
+            pool.free(second);
+        }
+    }
+
+    #[test]
+    fn test_pcap_big_endian() {
+        let mut file = Vec::new();
+        file.extend_from_slice(&PCAP_MAGIC_MICROS.to_be_bytes());
+        file.extend_from_slice(&[0, 2, 0, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff, 0, 0, 0, 101]);
+        file.extend_from_slice(&[0, 0, 0, 10, 0, 0, 0, 5, 0, 0, 0, 3, 0, 0, 0, 3]);
+        file.extend_from_slice(b"abc");
+
+        let pool = MPool::new(1, 16);
+        let mut reader = PcapReader::new(&file[..], &pool).unwrap();
+        assert_eq!((reader.linktype(), reader.snaplen()), (LINKTYPE_RAW, 65535));
+        let buf = reader.next().unwrap().unwrap();
+        assert_eq!(&buf[..], b"abc");
+        assert_eq!(buf.meta().timestamp, Some(Duration::new(10, 5_000)));
+
+        // 截断的报文
+        let mut reader = PcapReader::new(&file[..file.len() - 1], &pool).unwrap();
+        assert!(reader.next().unwrap().is_err());
+        assert!(PcapReader::new(&file[4..], &pool).is_err());
+    }
+
+    #[test]
+    fn test_pcapng_roundtrip() {
+        let pool = MPool::new(2, 16);
+        let ts = Duration::new(1_700_000_000, 987_654_321);
+        for nanos in [false, true] {
+            let mut writer = if nanos {
+                PcapNgWriter::with_nanos(Vec::new(), LINKTYPE_ETHERNET).unwrap()
+            } else {
+                PcapNgWriter::new(Vec::new(), LINKTYPE_ETHERNET).unwrap()
+            };
+            writer.write_packet(&packet(b"odd", ts)).unwrap();
+            let mut file = writer.into_inner();
+            // 未知块应被跳过
+            file.extend_from_slice(&[0xbd, 0x0b, 0, 0, 16, 0, 0, 0, 1, 2, 3, 4, 16, 0, 0, 0]);
+            let mut writer = PcapNgWriter { writer: file, nanos };
+            writer.write_chain(&MChain::from(packet(b"chained", ts))).unwrap();
+            let file = writer.into_inner();
+            assert_eq!(file.len() % 4, 0);
+
+            let mut reader = PcapNgReader::new(&file[..], &pool).unwrap();
+            let packets: Vec<MBuf> = reader.by_ref().map(|packet| packet.unwrap()).collect();
+            assert_eq!(reader.linktype(0), Some(LINKTYPE_ETHERNET));
+            assert_eq!(packets.len(), 2);
+            assert_eq!(&packets[0][..], b"odd");
+            assert_eq!(&packets[1][..], b"chained");
+            let expected = if nanos { ts } else { Duration::new(ts.as_secs(), 987_654_000) };
+            assert_eq!(packets[0].meta().timestamp, Some(expected));
+            for buf in packets {
+                pool.free(buf);
+            }
+        }
+    }
+
+    #[test]
+    fn test_pcapng_big_endian() {
+        let mut file = Vec::new();
+        file.extend_from_slice(&BLOCK_SHB.to_be_bytes());
+        file.extend_from_slice(&28u32.to_be_bytes());
+        file.extend_from_slice(&BYTE_ORDER_MAGIC.to_be_bytes());
+        file.extend_from_slice(&[0, 1, 0, 0, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]);
+        file.extend_from_slice(&28u32.to_be_bytes());
+        // 接口描述，if_tsresol = 2^-10
+        file.extend_from_slice(&[0, 0, 0, 1, 0, 0, 0, 32, 0, 101, 0, 0, 0, 0, 0, 0]);
+        file.extend_from_slice(&[0, 9, 0, 1, 0x8a, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 32]);
+        // 增强报文块，时间戳为1536/1024秒
+        file.extend_from_slice(&[0, 0, 0, 6, 0, 0, 0, 36, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 6, 0]);
+        file.extend_from_slice(&[0, 0, 0, 2, 0, 0, 0, 2, b'h', b'i', 0, 0, 0, 0, 0, 36]);
+
+        let pool = MPool::new(1, 16);
+        let mut reader = PcapNgReader::new(&file[..], &pool).unwrap();
+        let buf = reader.next_packet().unwrap().unwrap();
+        assert_eq!(reader.linktype(0), Some(LINKTYPE_RAW));
+        assert_eq!(&buf[..], b"hi");
+        assert_eq!(buf.meta().timestamp, Some(Duration::from_millis(1500)));
+        assert!(reader.next_packet().unwrap().is_none());
+    }
+}
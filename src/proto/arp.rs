@@ -0,0 +1,186 @@
+// MIT License
+//
+// Copyright (c) 2023 gaosg
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::net::Ipv4Addr;
+use super::{get_u16, set_u16, Error, Result};
+
+/// ARP报文视图
+pub struct ArpPacket<T> {
+    buffer: T,
+}
+
+impl<T: AsRef<[u8]>> ArpPacket<T> {
+    /// 固定头部长度
+    pub const HEADER_LEN: usize = 8;
+    /// 请求
+    pub const REQUEST: u16 = 1;
+    /// 应答
+    pub const REPLY: u16 = 2;
+
+    /// 校验长度并创建视图
+    pub fn new_checked(buffer: T) -> Result<Self> {
+        let data = buffer.as_ref();
+        if data.len() < Self::HEADER_LEN {
+            return Err(Error::Truncated);
+        }
+        let packet = Self { buffer };
+        if packet.buffer.as_ref().len() < packet.total_len() {
+            return Err(Error::Truncated);
+        }
+        Ok(packet)
+    }
+
+    /// 取回底层缓冲区
+    pub fn into_inner(self) -> T {
+        self.buffer
+    }
+
+    /// 包括地址在内的报文长度
+    pub fn total_len(&self) -> usize {
+        Self::HEADER_LEN + 2 * (self.hardware_len() as usize + self.protocol_len() as usize)
+    }
+
+    /// 硬件类型，以太网为1
+    pub fn hardware_type(&self) -> u16 {
+        get_u16(self.buffer.as_ref(), 0)
+    }
+
+    /// 协议类型，IPv4为0x0800
+    pub fn protocol_type(&self) -> u16 {
+        get_u16(self.buffer.as_ref(), 2)
+    }
+
+    /// 硬件地址长度
+    pub fn hardware_len(&self) -> u8 {
+        self.buffer.as_ref()[4]
+    }
+
+    /// 协议地址长度
+    pub fn protocol_len(&self) -> u8 {
+        self.buffer.as_ref()[5]
+    }
+
+    /// 操作码
+    pub fn operation(&self) -> u16 {
+        get_u16(self.buffer.as_ref(), 6)
+    }
+
+    /// 发送方硬件地址
+    pub fn sender_hardware_addr(&self) -> &[u8] {
+        let (start, _, _, _) = self.offsets();
+        &self.buffer.as_ref()[start..start + self.hardware_len() as usize]
+    }
+
+    /// 发送方协议地址
+    pub fn sender_protocol_addr(&self) -> &[u8] {
+        let (_, start, _, _) = self.offsets();
+        &self.buffer.as_ref()[start..start + self.protocol_len() as usize]
+    }
+
+    /// 目标硬件地址
+    pub fn target_hardware_addr(&self) -> &[u8] {
+        let (_, _, start, _) = self.offsets();
+        &self.buffer.as_ref()[start..start + self.hardware_len() as usize]
+    }
+
+    /// 目标协议地址
+    pub fn target_protocol_addr(&self) -> &[u8] {
+        let (_, _, _, start) = self.offsets();
+        &self.buffer.as_ref()[start..start + self.protocol_len() as usize]
+    }
+
+    /// 发送方IPv4地址，协议地址长度不是4时返回None
+    pub fn sender_ipv4(&self) -> Option<Ipv4Addr> {
+        <[u8; 4]>::try_from(self.sender_protocol_addr()).ok().map(Ipv4Addr::from)
+    }
+
+    /// 目标IPv4地址，协议地址长度不是4时返回None
+    pub fn target_ipv4(&self) -> Option<Ipv4Addr> {
+        <[u8; 4]>::try_from(self.target_protocol_addr()).ok().map(Ipv4Addr::from)
+    }
+
+    /// 四个地址字段的起始偏移
+    fn offsets(&self) -> (usize, usize, usize, usize) {
+        let hlen = self.hardware_len() as usize;
+        let plen = self.protocol_len() as usize;
+        let sha = Self::HEADER_LEN;
+        (sha, sha + hlen, sha + hlen + plen, sha + 2 * hlen + plen)
+    }
+}
+
+impl<T: AsRef<[u8]> + AsMut<[u8]>> ArpPacket<T> {
+    /// 设置操作码
+    pub fn set_operation(&mut self, value: u16) {
+        set_u16(self.buffer.as_mut(), 6, value);
+    }
+
+    /// 设置发送方硬件地址，长度须与hardware_len一致
+    pub fn set_sender_hardware_addr(&mut self, addr: &[u8]) {
+        let (start, _, _, _) = self.offsets();
+        self.buffer.as_mut()[start..start + addr.len()].copy_from_slice(addr);
+    }
+
+    /// 设置发送方协议地址，长度须与protocol_len一致
+    pub fn set_sender_protocol_addr(&mut self, addr: &[u8]) {
+        let (_, start, _, _) = self.offsets();
+        self.buffer.as_mut()[start..start + addr.len()].copy_from_slice(addr);
+    }
+
+    /// 设置目标硬件地址，长度须与hardware_len一致
+    pub fn set_target_hardware_addr(&mut self, addr: &[u8]) {
+        let (_, _, start, _) = self.offsets();
+        self.buffer.as_mut()[start..start + addr.len()].copy_from_slice(addr);
+    }
+
+    /// 设置目标协议地址，长度须与protocol_len一致
+    pub fn set_target_protocol_addr(&mut self, addr: &[u8]) {
+        let (_, _, _, start) = self.offsets();
+        self.buffer.as_mut()[start..start + addr.len()].copy_from_slice(addr);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arp_reply_in_place() {
+        let mut request = [
+            0x00, 0x01, 0x08, 0x00, 0x06, 0x04, 0x00, 0x01,
+            0x02, 0x00, 0x00, 0x00, 0x00, 0x01, 10, 0, 0, 1,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 10, 0, 0, 2,
+        ];
+        let mut arp = ArpPacket::new_checked(&mut request[..]).unwrap();
+        assert_eq!(arp.operation(), ArpPacket::<&[u8]>::REQUEST);
+        assert_eq!(arp.target_ipv4(), Some(Ipv4Addr::new(10, 0, 0, 2)));
+
+        let sender = arp.sender_hardware_addr().to_vec();
+        arp.set_operation(ArpPacket::<&[u8]>::REPLY);
+        arp.set_target_hardware_addr(&sender);
+        arp.set_target_protocol_addr(&[10, 0, 0, 1]);
+        arp.set_sender_hardware_addr(&[0x02, 0, 0, 0, 0, 0x02]);
+        arp.set_sender_protocol_addr(&[10, 0, 0, 2]);
+        assert_eq!(arp.sender_ipv4(), Some(Ipv4Addr::new(10, 0, 0, 2)));
+        assert_eq!(arp.target_hardware_addr(), &sender[..]);
+        assert_eq!(ArpPacket::new_checked(&request[..20]).err(), Some(Error::Truncated));
+    }
+}
@@ -0,0 +1,181 @@
+// MIT License
+//
+// Copyright (c) 2023 gaosg
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use super::{ethertype, get_u16, set_u16, Error, Result};
+
+/// 802.1Q VLAN标签
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VlanTag {
+    /// 优先级
+    pub pcp: u8,
+    /// 可丢弃标识
+    pub dei: bool,
+    /// VLAN ID
+    pub vid: u16,
+}
+
+impl VlanTag {
+    fn from_tci(tci: u16) -> Self {
+        Self {
+            pcp: (tci >> 13) as u8,
+            dei: tci & 0x1000 != 0,
+            vid: tci & 0x0fff,
+        }
+    }
+
+    fn to_tci(self) -> u16 {
+        ((self.pcp as u16 & 0x7) << 13) | ((self.dei as u16) << 12) | (self.vid & 0x0fff)
+    }
+}
+
+/// Ethernet II帧视图，支持802.1Q及QinQ标签
+pub struct EthernetFrame<T> {
+    buffer: T,
+    header_len: usize,
+}
+
+impl<T: AsRef<[u8]>> EthernetFrame<T> {
+    /// 不带标签的以太网头长度
+    pub const HEADER_LEN: usize = 14;
+
+    /// 校验长度并创建视图
+    pub fn new_checked(buffer: T) -> Result<Self> {
+        let data = buffer.as_ref();
+        if data.len() < Self::HEADER_LEN {
+            return Err(Error::Truncated);
+        }
+        let mut header_len = Self::HEADER_LEN;
+        while matches!(get_u16(data, header_len - 2), ethertype::VLAN | ethertype::QINQ) {
+            header_len += 4;
+            if data.len() < header_len {
+                return Err(Error::Truncated);
+            }
+        }
+        Ok(Self { buffer, header_len })
+    }
+
+    /// 取回底层缓冲区
+    pub fn into_inner(self) -> T {
+        self.buffer
+    }
+
+    /// 获取包括VLAN标签在内的头部长度
+    pub fn header_len(&self) -> usize {
+        self.header_len
+    }
+
+    /// 目的MAC地址
+    pub fn dst(&self) -> [u8; 6] {
+        self.buffer.as_ref()[0..6].try_into().unwrap()
+    }
+
+    /// 源MAC地址
+    pub fn src(&self) -> [u8; 6] {
+        self.buffer.as_ref()[6..12].try_into().unwrap()
+    }
+
+    /// 最外层的以太网类型，带标签时为0x8100或0x88a8
+    pub fn ethertype(&self) -> u16 {
+        get_u16(self.buffer.as_ref(), 12)
+    }
+
+    /// 跳过所有VLAN标签后负载的以太网类型
+    pub fn payload_ethertype(&self) -> u16 {
+        get_u16(self.buffer.as_ref(), self.header_len - 2)
+    }
+
+    /// 最外层的VLAN标签
+    pub fn vlan(&self) -> Option<VlanTag> {
+        self.vlans().next()
+    }
+
+    /// 由外到内遍历VLAN标签
+    pub fn vlans(&self) -> impl Iterator<Item = VlanTag> + '_ {
+        (Self::HEADER_LEN..self.header_len)
+            .step_by(4)
+            .map(|offset| VlanTag::from_tci(get_u16(self.buffer.as_ref(), offset)))
+    }
+
+    /// 负载数据
+    pub fn payload(&self) -> &[u8] {
+        &self.buffer.as_ref()[self.header_len..]
+    }
+}
+
+impl<T: AsRef<[u8]> + AsMut<[u8]>> EthernetFrame<T> {
+    /// 设置目的MAC地址
+    pub fn set_dst(&mut self, addr: [u8; 6]) {
+        self.buffer.as_mut()[0..6].copy_from_slice(&addr);
+    }
+
+    /// 设置源MAC地址
+    pub fn set_src(&mut self, addr: [u8; 6]) {
+        self.buffer.as_mut()[6..12].copy_from_slice(&addr);
+    }
+
+    /// 设置负载的以太网类型
+    pub fn set_payload_ethertype(&mut self, value: u16) {
+        let offset = self.header_len - 2;
+        set_u16(self.buffer.as_mut(), offset, value);
+    }
+
+    /// 改写最外层的VLAN标签，没有标签时返回false
+    pub fn set_vlan(&mut self, tag: VlanTag) -> bool {
+        if self.header_len == Self::HEADER_LEN {
+            return false;
+        }
+        set_u16(self.buffer.as_mut(), Self::HEADER_LEN, tag.to_tci());
+        true
+    }
+
+    /// 可变的负载数据
+    pub fn payload_mut(&mut self) -> &mut [u8] {
+        let header_len = self.header_len;
+        &mut self.buffer.as_mut()[header_len..]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vlan() {
+        let mut frame = [0u8; 26];
+        frame[12..14].copy_from_slice(&ethertype::QINQ.to_be_bytes());
+        frame[14..16].copy_from_slice(&0x2064u16.to_be_bytes());
+        frame[16..18].copy_from_slice(&ethertype::VLAN.to_be_bytes());
+        frame[18..20].copy_from_slice(&0x0005u16.to_be_bytes());
+        frame[20..22].copy_from_slice(&ethertype::IPV6.to_be_bytes());
+
+        let mut eth = EthernetFrame::new_checked(&mut frame[..]).unwrap();
+        assert_eq!(eth.header_len(), 22);
+        assert_eq!(eth.payload_ethertype(), ethertype::IPV6);
+        assert_eq!(eth.vlan(), Some(VlanTag { pcp: 1, dei: false, vid: 100 }));
+        assert_eq!(eth.vlans().nth(1).unwrap().vid, 5);
+        assert_eq!(eth.payload().len(), 4);
+
+        assert!(eth.set_vlan(VlanTag { pcp: 7, dei: true, vid: 42 }));
+        assert_eq!(eth.vlan(), Some(VlanTag { pcp: 7, dei: true, vid: 42 }));
+        assert_eq!(EthernetFrame::new_checked(&frame[..20]).err(), Some(Error::Truncated));
+    }
+}
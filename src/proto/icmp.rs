@@ -0,0 +1,161 @@
+// MIT License
+//
+// Copyright (c) 2023 gaosg
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::net::IpAddr;
+use super::{get_u16, ip_protocol, set_u16, transport_checksum, Error, Result};
+use crate::checksum::internet_checksum;
+
+/// ICMP（及ICMPv6）报文视图
+pub struct IcmpPacket<T> {
+    buffer: T,
+}
+
+impl<T: AsRef<[u8]>> IcmpPacket<T> {
+    /// 头部长度，包括4字节的类型相关字段
+    pub const HEADER_LEN: usize = 8;
+    /// 回显应答
+    pub const ECHO_REPLY: u8 = 0;
+    /// 回显请求
+    pub const ECHO_REQUEST: u8 = 8;
+
+    /// 校验长度并创建视图
+    pub fn new_checked(buffer: T) -> Result<Self> {
+        if buffer.as_ref().len() < Self::HEADER_LEN {
+            return Err(Error::Truncated);
+        }
+        Ok(Self { buffer })
+    }
+
+    /// 取回底层缓冲区
+    pub fn into_inner(self) -> T {
+        self.buffer
+    }
+
+    /// 消息类型
+    pub fn msg_type(&self) -> u8 {
+        self.buffer.as_ref()[0]
+    }
+
+    /// 消息代码
+    pub fn code(&self) -> u8 {
+        self.buffer.as_ref()[1]
+    }
+
+    /// 校验和
+    pub fn checksum(&self) -> u16 {
+        get_u16(self.buffer.as_ref(), 2)
+    }
+
+    /// 回显报文的标识
+    pub fn echo_ident(&self) -> u16 {
+        get_u16(self.buffer.as_ref(), 4)
+    }
+
+    /// 回显报文的序号
+    pub fn echo_seq(&self) -> u16 {
+        get_u16(self.buffer.as_ref(), 6)
+    }
+
+    /// 类型相关的4字节字段
+    pub fn rest_of_header(&self) -> [u8; 4] {
+        self.buffer.as_ref()[4..8].try_into().unwrap()
+    }
+
+    /// 负载数据
+    pub fn payload(&self) -> &[u8] {
+        &self.buffer.as_ref()[Self::HEADER_LEN..]
+    }
+
+    /// ICMPv4校验和是否正确
+    pub fn verify_checksum(&self) -> bool {
+        internet_checksum(self.buffer.as_ref()) == 0
+    }
+
+    /// 按IPv6伪首部校验ICMPv6校验和
+    pub fn verify_checksum_v6(&self, src: IpAddr, dst: IpAddr) -> bool {
+        transport_checksum(self.buffer.as_ref(), 2, src, dst, ip_protocol::ICMPV6) == self.checksum()
+    }
+}
+
+impl<T: AsRef<[u8]> + AsMut<[u8]>> IcmpPacket<T> {
+    /// 设置消息类型
+    pub fn set_msg_type(&mut self, value: u8) {
+        self.buffer.as_mut()[0] = value;
+    }
+
+    /// 设置消息代码
+    pub fn set_code(&mut self, value: u8) {
+        self.buffer.as_mut()[1] = value;
+    }
+
+    /// 设置回显报文的标识
+    pub fn set_echo_ident(&mut self, value: u16) {
+        set_u16(self.buffer.as_mut(), 4, value);
+    }
+
+    /// 设置回显报文的序号
+    pub fn set_echo_seq(&mut self, value: u16) {
+        set_u16(self.buffer.as_mut(), 6, value);
+    }
+
+    /// 重新计算并填写ICMPv4校验和
+    pub fn fill_checksum(&mut self) {
+        set_u16(self.buffer.as_mut(), 2, 0);
+        let checksum = internet_checksum(self.buffer.as_ref());
+        set_u16(self.buffer.as_mut(), 2, checksum);
+    }
+
+    /// 按IPv6伪首部重新计算并填写ICMPv6校验和
+    pub fn fill_checksum_v6(&mut self, src: IpAddr, dst: IpAddr) {
+        let checksum = transport_checksum(self.buffer.as_ref(), 2, src, dst, ip_protocol::ICMPV6);
+        set_u16(self.buffer.as_mut(), 2, checksum);
+    }
+
+    /// 可变的负载数据
+    pub fn payload_mut(&mut self) -> &mut [u8] {
+        &mut self.buffer.as_mut()[Self::HEADER_LEN..]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_echo_reply_in_place() {
+        let mut packet = [8, 0, 0, 0, 0x12, 0x34, 0x00, 0x01, b'h', b'i'];
+        let mut icmp = IcmpPacket::new_checked(&mut packet[..]).unwrap();
+        icmp.fill_checksum();
+        assert!(icmp.verify_checksum());
+        assert_eq!((icmp.echo_ident(), icmp.echo_seq()), (0x1234, 1));
+
+        icmp.set_msg_type(IcmpPacket::<&[u8]>::ECHO_REPLY);
+        assert!(!icmp.verify_checksum());
+        icmp.fill_checksum();
+        assert!(icmp.verify_checksum());
+
+        let (src, dst) = ("fe80::1".parse().unwrap(), "fe80::2".parse().unwrap());
+        icmp.fill_checksum_v6(src, dst);
+        assert!(icmp.verify_checksum_v6(src, dst));
+        assert_eq!(icmp.payload(), b"hi");
+    }
+}
@@ -0,0 +1,311 @@
+// MIT License
+//
+// Copyright (c) 2023 gaosg
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::net::Ipv4Addr;
+use super::{get_u16, set_u16, Error, Result};
+use crate::checksum::internet_checksum;
+
+/// IPv4报文视图
+pub struct Ipv4Packet<T> {
+    buffer: T,
+}
+
+impl<T: AsRef<[u8]>> Ipv4Packet<T> {
+    /// 不带选项的头部长度
+    pub const MIN_HEADER_LEN: usize = 20;
+
+    /// 校验版本、头部长度和总长度并创建视图
+    pub fn new_checked(buffer: T) -> Result<Self> {
+        let data = buffer.as_ref();
+        if data.len() < Self::MIN_HEADER_LEN {
+            return Err(Error::Truncated);
+        }
+        let packet = Self { buffer };
+        if packet.version() != 4 || packet.header_len() < Self::MIN_HEADER_LEN {
+            return Err(Error::Malformed);
+        }
+        let total_len = packet.total_len() as usize;
+        if total_len < packet.header_len() {
+            return Err(Error::Malformed);
+        }
+        if packet.buffer.as_ref().len() < total_len {
+            return Err(Error::Truncated);
+        }
+        Ok(packet)
+    }
+
+    /// 取回底层缓冲区
+    pub fn into_inner(self) -> T {
+        self.buffer
+    }
+
+    /// 版本号
+    pub fn version(&self) -> u8 {
+        self.buffer.as_ref()[0] >> 4
+    }
+
+    /// 包括选项在内的头部长度（字节）
+    pub fn header_len(&self) -> usize {
+        (self.buffer.as_ref()[0] & 0x0f) as usize * 4
+    }
+
+    /// 区分服务代码点
+    pub fn dscp(&self) -> u8 {
+        self.buffer.as_ref()[1] >> 2
+    }
+
+    /// 显式拥塞通知
+    pub fn ecn(&self) -> u8 {
+        self.buffer.as_ref()[1] & 0x03
+    }
+
+    /// 总长度
+    pub fn total_len(&self) -> u16 {
+        get_u16(self.buffer.as_ref(), 2)
+    }
+
+    /// 标识
+    pub fn ident(&self) -> u16 {
+        get_u16(self.buffer.as_ref(), 4)
+    }
+
+    /// 不分片标志
+    pub fn dont_frag(&self) -> bool {
+        self.buffer.as_ref()[6] & 0x40 != 0
+    }
+
+    /// 更多分片标志
+    pub fn more_frags(&self) -> bool {
+        self.buffer.as_ref()[6] & 0x20 != 0
+    }
+
+    /// 分片偏移（字节）
+    pub fn frag_offset(&self) -> usize {
+        (get_u16(self.buffer.as_ref(), 6) & 0x1fff) as usize * 8
+    }
+
+    /// 生存时间
+    pub fn ttl(&self) -> u8 {
+        self.buffer.as_ref()[8]
+    }
+
+    /// 上层协议号
+    pub fn protocol(&self) -> u8 {
+        self.buffer.as_ref()[9]
+    }
+
+    /// 头部校验和
+    pub fn checksum(&self) -> u16 {
+        get_u16(self.buffer.as_ref(), 10)
+    }
+
+    /// 源地址
+    pub fn src(&self) -> Ipv4Addr {
+        Ipv4Addr::from(<[u8; 4]>::try_from(&self.buffer.as_ref()[12..16]).unwrap())
+    }
+
+    /// 目的地址
+    pub fn dst(&self) -> Ipv4Addr {
+        Ipv4Addr::from(<[u8; 4]>::try_from(&self.buffer.as_ref()[16..20]).unwrap())
+    }
+
+    /// 选项的原始数据
+    pub fn options(&self) -> &[u8] {
+        &self.buffer.as_ref()[Self::MIN_HEADER_LEN..self.header_len()]
+    }
+
+    /// 遍历选项
+    pub fn option_iter(&self) -> Ipv4Options<'_> {
+        Ipv4Options { data: self.options() }
+    }
+
+    /// 头部校验和是否正确
+    pub fn verify_checksum(&self) -> bool {
+        internet_checksum(&self.buffer.as_ref()[..self.header_len()]) == 0
+    }
+
+    /// 负载数据，不含总长度之后的填充
+    pub fn payload(&self) -> &[u8] {
+        let data = self.buffer.as_ref();
+        let end = (self.total_len() as usize).clamp(self.header_len(), data.len());
+        &data[self.header_len()..end]
+    }
+}
+
+impl<T: AsRef<[u8]> + AsMut<[u8]>> Ipv4Packet<T> {
+    /// 设置区分服务代码点
+    pub fn set_dscp(&mut self, value: u8) {
+        let data = self.buffer.as_mut();
+        data[1] = (value << 2) | (data[1] & 0x03);
+    }
+
+    /// 设置显式拥塞通知
+    pub fn set_ecn(&mut self, value: u8) {
+        let data = self.buffer.as_mut();
+        data[1] = (data[1] & 0xfc) | (value & 0x03);
+    }
+
+    /// 设置总长度
+    pub fn set_total_len(&mut self, value: u16) {
+        set_u16(self.buffer.as_mut(), 2, value);
+    }
+
+    /// 设置标识
+    pub fn set_ident(&mut self, value: u16) {
+        set_u16(self.buffer.as_mut(), 4, value);
+    }
+
+    /// 设置不分片标志
+    pub fn set_dont_frag(&mut self, value: bool) {
+        let data = self.buffer.as_mut();
+        data[6] = if value { data[6] | 0x40 } else { data[6] & !0x40 };
+    }
+
+    /// 设置更多分片标志
+    pub fn set_more_frags(&mut self, value: bool) {
+        let data = self.buffer.as_mut();
+        data[6] = if value { data[6] | 0x20 } else { data[6] & !0x20 };
+    }
+
+    /// 设置分片偏移（字节，须为8的倍数）
+    pub fn set_frag_offset(&mut self, value: usize) {
+        let flags = get_u16(self.buffer.as_ref(), 6) & 0xe000;
+        set_u16(self.buffer.as_mut(), 6, flags | ((value / 8) as u16 & 0x1fff));
+    }
+
+    /// 设置生存时间
+    pub fn set_ttl(&mut self, value: u8) {
+        self.buffer.as_mut()[8] = value;
+    }
+
+    /// 设置上层协议号
+    pub fn set_protocol(&mut self, value: u8) {
+        self.buffer.as_mut()[9] = value;
+    }
+
+    /// 设置源地址
+    pub fn set_src(&mut self, addr: Ipv4Addr) {
+        self.buffer.as_mut()[12..16].copy_from_slice(&addr.octets());
+    }
+
+    /// 设置目的地址
+    pub fn set_dst(&mut self, addr: Ipv4Addr) {
+        self.buffer.as_mut()[16..20].copy_from_slice(&addr.octets());
+    }
+
+    /// 重新计算并填写头部校验和
+    pub fn fill_checksum(&mut self) {
+        set_u16(self.buffer.as_mut(), 10, 0);
+        let checksum = internet_checksum(&self.buffer.as_ref()[..self.header_len()]);
+        set_u16(self.buffer.as_mut(), 10, checksum);
+    }
+
+    /// 可变的选项数据
+    pub fn options_mut(&mut self) -> &mut [u8] {
+        let header_len = self.header_len();
+        &mut self.buffer.as_mut()[Self::MIN_HEADER_LEN..header_len]
+    }
+
+    /// 可变的负载数据
+    pub fn payload_mut(&mut self) -> &mut [u8] {
+        let header_len = self.header_len();
+        let len = self.buffer.as_ref().len();
+        let end = (self.total_len() as usize).clamp(header_len, len);
+        &mut self.buffer.as_mut()[header_len..end]
+    }
+}
+
+/// IPv4选项
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ipv4Option<'a> {
+    /// 选项列表结束
+    End,
+    /// 填充
+    Nop,
+    /// 其他选项，data不含类型和长度字节
+    Other { kind: u8, data: &'a [u8] },
+}
+
+/// IPv4选项迭代器，遇到格式错误的选项时返回Err并停止
+pub struct Ipv4Options<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Iterator for Ipv4Options<'a> {
+    type Item = Result<Ipv4Option<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (&kind, rest) = self.data.split_first()?;
+        match kind {
+            0 => {
+                self.data = &[];
+                Some(Ok(Ipv4Option::End))
+            }
+            1 => {
+                self.data = rest;
+                Some(Ok(Ipv4Option::Nop))
+            }
+            _ => {
+                let len = rest.first().map_or(0, |&len| len as usize);
+                if len < 2 || len > self.data.len() {
+                    self.data = &[];
+                    return Some(Err(Error::Malformed));
+                }
+                let data = &self.data[2..len];
+                self.data = &self.data[len..];
+                Some(Ok(Ipv4Option::Other { kind, data }))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_options_and_fragments() {
+        let mut packet = [0u8; 28];
+        packet[0] = 0x46;
+        packet[2..4].copy_from_slice(&28u16.to_be_bytes());
+        packet[20..24].copy_from_slice(&[0x94, 0x04, 0x00, 0x00]);
+
+        let mut ip = Ipv4Packet::new_checked(&mut packet[..]).unwrap();
+        assert_eq!(ip.header_len(), 24);
+        let options: Vec<_> = ip.option_iter().collect();
+        assert_eq!(options, vec![Ok(Ipv4Option::Other { kind: 0x94, data: &[0, 0] })]);
+
+        ip.set_more_frags(true);
+        ip.set_frag_offset(1480);
+        assert!(ip.more_frags() && !ip.dont_frag());
+        assert_eq!(ip.frag_offset(), 1480);
+        ip.set_dscp(46);
+        ip.set_ecn(1);
+        assert_eq!((ip.dscp(), ip.ecn()), (46, 1));
+        assert_eq!(ip.payload().len(), 4);
+
+        packet[0] = 0x44;
+        assert_eq!(Ipv4Packet::new_checked(&packet[..]).err(), Some(Error::Malformed));
+        packet[0] = 0x4f;
+        assert_eq!(Ipv4Packet::new_checked(&packet[..]).err(), Some(Error::Malformed));
+    }
+}
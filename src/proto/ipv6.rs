@@ -0,0 +1,253 @@
+// MIT License
+//
+// Copyright (c) 2023 gaosg
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::net::Ipv6Addr;
+use super::{get_u16, get_u32, ip_protocol, set_u16, set_u32, Error, Result};
+
+/// IPv6报文视图
+pub struct Ipv6Packet<T> {
+    buffer: T,
+}
+
+impl<T: AsRef<[u8]>> Ipv6Packet<T> {
+    /// 固定头部长度
+    pub const HEADER_LEN: usize = 40;
+
+    /// 校验版本和负载长度并创建视图
+    pub fn new_checked(buffer: T) -> Result<Self> {
+        let data = buffer.as_ref();
+        if data.len() < Self::HEADER_LEN {
+            return Err(Error::Truncated);
+        }
+        let packet = Self { buffer };
+        if packet.version() != 6 {
+            return Err(Error::Malformed);
+        }
+        if packet.buffer.as_ref().len() < Self::HEADER_LEN + packet.payload_len() as usize {
+            return Err(Error::Truncated);
+        }
+        Ok(packet)
+    }
+
+    /// 取回底层缓冲区
+    pub fn into_inner(self) -> T {
+        self.buffer
+    }
+
+    /// 版本号
+    pub fn version(&self) -> u8 {
+        self.buffer.as_ref()[0] >> 4
+    }
+
+    /// 流量类别
+    pub fn traffic_class(&self) -> u8 {
+        (get_u16(self.buffer.as_ref(), 0) >> 4) as u8
+    }
+
+    /// 流标签
+    pub fn flow_label(&self) -> u32 {
+        get_u32(self.buffer.as_ref(), 0) & 0x000f_ffff
+    }
+
+    /// 负载长度，包括扩展头
+    pub fn payload_len(&self) -> u16 {
+        get_u16(self.buffer.as_ref(), 4)
+    }
+
+    /// 紧跟固定头部的下一个头部类型
+    pub fn next_header(&self) -> u8 {
+        self.buffer.as_ref()[6]
+    }
+
+    /// 跳数限制
+    pub fn hop_limit(&self) -> u8 {
+        self.buffer.as_ref()[7]
+    }
+
+    /// 源地址
+    pub fn src(&self) -> Ipv6Addr {
+        Ipv6Addr::from(<[u8; 16]>::try_from(&self.buffer.as_ref()[8..24]).unwrap())
+    }
+
+    /// 目的地址
+    pub fn dst(&self) -> Ipv6Addr {
+        Ipv6Addr::from(<[u8; 16]>::try_from(&self.buffer.as_ref()[24..40]).unwrap())
+    }
+
+    /// 固定头部之后的数据，包括扩展头
+    pub fn payload(&self) -> &[u8] {
+        let data = self.buffer.as_ref();
+        let end = (Self::HEADER_LEN + self.payload_len() as usize).min(data.len());
+        &data[Self::HEADER_LEN..end]
+    }
+
+    /// 遍历扩展头
+    pub fn ext_headers(&self) -> ExtHeaders<'_> {
+        ExtHeaders {
+            next_header: self.next_header(),
+            data: self.payload(),
+            offset: 0,
+            done: false,
+        }
+    }
+
+    /// 跳过所有扩展头，返回上层协议号及其数据；遇到ESP等无法跳过的头部时返回该头部
+    pub fn upper_layer(&self) -> Result<(u8, &[u8])> {
+        let mut headers = self.ext_headers();
+        for header in &mut headers {
+            header?;
+        }
+        Ok((headers.next_header, &self.payload()[headers.offset..]))
+    }
+}
+
+impl<T: AsRef<[u8]> + AsMut<[u8]>> Ipv6Packet<T> {
+    /// 设置流量类别
+    pub fn set_traffic_class(&mut self, value: u8) {
+        let word = get_u16(self.buffer.as_ref(), 0) & 0xf00f;
+        set_u16(self.buffer.as_mut(), 0, word | ((value as u16) << 4));
+    }
+
+    /// 设置流标签
+    pub fn set_flow_label(&mut self, value: u32) {
+        let word = get_u32(self.buffer.as_ref(), 0) & 0xfff0_0000;
+        set_u32(self.buffer.as_mut(), 0, word | (value & 0x000f_ffff));
+    }
+
+    /// 设置负载长度
+    pub fn set_payload_len(&mut self, value: u16) {
+        set_u16(self.buffer.as_mut(), 4, value);
+    }
+
+    /// 设置下一个头部类型
+    pub fn set_next_header(&mut self, value: u8) {
+        self.buffer.as_mut()[6] = value;
+    }
+
+    /// 设置跳数限制
+    pub fn set_hop_limit(&mut self, value: u8) {
+        self.buffer.as_mut()[7] = value;
+    }
+
+    /// 设置源地址
+    pub fn set_src(&mut self, addr: Ipv6Addr) {
+        self.buffer.as_mut()[8..24].copy_from_slice(&addr.octets());
+    }
+
+    /// 设置目的地址
+    pub fn set_dst(&mut self, addr: Ipv6Addr) {
+        self.buffer.as_mut()[24..40].copy_from_slice(&addr.octets());
+    }
+
+    /// 可变的负载数据
+    pub fn payload_mut(&mut self) -> &mut [u8] {
+        let end = (Self::HEADER_LEN + self.payload_len() as usize).min(self.buffer.as_ref().len());
+        &mut self.buffer.as_mut()[Self::HEADER_LEN..end]
+    }
+}
+
+/// IPv6扩展头
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExtHeader<'a> {
+    /// 扩展头类型
+    pub kind: u8,
+    /// 本扩展头之后的头部类型
+    pub next_header: u8,
+    /// 整个扩展头的数据，包括下一头部和长度字段
+    pub data: &'a [u8],
+}
+
+/// IPv6扩展头迭代器，到达上层协议或无法解析的头部时停止
+pub struct ExtHeaders<'a> {
+    next_header: u8,
+    data: &'a [u8],
+    offset: usize,
+    done: bool,
+}
+
+impl<'a> Iterator for ExtHeaders<'a> {
+    type Item = Result<ExtHeader<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let kind = self.next_header;
+        let rest = &self.data[self.offset..];
+        let len = match kind {
+            ip_protocol::HOP_BY_HOP | ip_protocol::ROUTING | ip_protocol::DESTINATION_OPTIONS => {
+                rest.get(1).map(|&len| (len as usize + 1) * 8)
+            }
+            ip_protocol::FRAGMENT => Some(8),
+            ip_protocol::AH => rest.get(1).map(|&len| (len as usize + 2) * 4),
+            _ => {
+                self.done = true;
+                return None;
+            }
+        };
+        match len {
+            Some(len) if len <= rest.len() => {
+                self.next_header = rest[0];
+                self.offset += len;
+                Some(Ok(ExtHeader { kind, next_header: rest[0], data: &rest[..len] }))
+            }
+            _ => {
+                self.done = true;
+                Some(Err(Error::Truncated))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ext_header_walk() {
+        let mut packet = vec![0u8; 40];
+        packet[0] = 0x60;
+        packet[6] = ip_protocol::HOP_BY_HOP;
+        packet[7] = 64;
+        // 逐跳选项（8字节）-> 分片头（8字节）-> UDP
+        packet.extend_from_slice(&[ip_protocol::FRAGMENT, 0, 1, 4, 0, 0, 0, 0]);
+        packet.extend_from_slice(&[ip_protocol::UDP, 0, 0, 0, 0, 0, 0, 1]);
+        packet.extend_from_slice(b"udp!");
+        let payload_len = (packet.len() - 40) as u16;
+        packet[4..6].copy_from_slice(&payload_len.to_be_bytes());
+
+        let mut ip = Ipv6Packet::new_checked(&mut packet[..]).unwrap();
+        let kinds: Vec<u8> = ip.ext_headers().map(|header| header.unwrap().kind).collect();
+        assert_eq!(kinds, vec![ip_protocol::HOP_BY_HOP, ip_protocol::FRAGMENT]);
+        assert_eq!(ip.upper_layer().unwrap(), (ip_protocol::UDP, &b"udp!"[..]));
+
+        ip.set_traffic_class(0xb8);
+        ip.set_flow_label(0x12345);
+        ip.set_hop_limit(1);
+        assert_eq!((ip.version(), ip.traffic_class(), ip.flow_label()), (6, 0xb8, 0x12345));
+
+        ip.set_payload_len(4);
+        assert_eq!(ip.upper_layer().err(), Some(Error::Truncated));
+        packet[4..6].copy_from_slice(&100u16.to_be_bytes());
+        assert_eq!(Ipv6Packet::new_checked(&packet[..]).err(), Some(Error::Truncated));
+    }
+}
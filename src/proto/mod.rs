@@ -0,0 +1,201 @@
+// MIT License
+//
+// Copyright (c) 2023 gaosg
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! 零拷贝、带边界检查的协议头视图
+//!
+//! 每种视图包装任意`T: AsRef<[u8]>`，例如`&MBuf`、`&[u8]`或`Cursor::remaining_slice()`，
+//! `new_checked`校验长度后字段访问不会越界；当`T`同时实现`AsMut<[u8]>`时可以改写字段并重新计算校验和
+
+mod arp;
+mod ethernet;
+mod icmp;
+mod ipv4;
+mod ipv6;
+mod tcp;
+mod udp;
+
+use std::fmt;
+use std::net::IpAddr;
+use crate::checksum::Checksum;
+
+pub use arp::ArpPacket;
+pub use ethernet::{EthernetFrame, VlanTag};
+pub use icmp::IcmpPacket;
+pub use ipv4::{Ipv4Option, Ipv4Options, Ipv4Packet};
+pub use ipv6::{ExtHeader, ExtHeaders, Ipv6Packet};
+pub use tcp::{TcpOption, TcpOptions, TcpPacket};
+pub use udp::UdpPacket;
+
+/// 以太网类型
+pub mod ethertype {
+    pub const IPV4: u16 = 0x0800;
+    pub const ARP: u16 = 0x0806;
+    pub const VLAN: u16 = 0x8100;
+    pub const QINQ: u16 = 0x88a8;
+    pub const IPV6: u16 = 0x86dd;
+}
+
+/// IP协议号及IPv6扩展头类型
+pub mod ip_protocol {
+    pub const HOP_BY_HOP: u8 = 0;
+    pub const ICMP: u8 = 1;
+    pub const TCP: u8 = 6;
+    pub const UDP: u8 = 17;
+    pub const ROUTING: u8 = 43;
+    pub const FRAGMENT: u8 = 44;
+    pub const ESP: u8 = 50;
+    pub const AH: u8 = 51;
+    pub const ICMPV6: u8 = 58;
+    pub const NO_NEXT_HEADER: u8 = 59;
+    pub const DESTINATION_OPTIONS: u8 = 60;
+}
+
+/// 协议头解析错误
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// 数据长度不足以容纳协议头或其声明的长度
+    Truncated,
+    /// 字段取值不合法
+    Malformed,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Truncated => write!(f, "packet is truncated"),
+            Error::Malformed => write!(f, "packet is malformed"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// 协议头解析结果
+pub type Result<T> = std::result::Result<T, Error>;
+
+fn get_u16(data: &[u8], offset: usize) -> u16 {
+    u16::from_be_bytes([data[offset], data[offset + 1]])
+}
+
+fn get_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]])
+}
+
+fn set_u16(data: &mut [u8], offset: usize, value: u16) {
+    data[offset..offset + 2].copy_from_slice(&value.to_be_bytes());
+}
+
+fn set_u32(data: &mut [u8], offset: usize, value: u32) {
+    data[offset..offset + 4].copy_from_slice(&value.to_be_bytes());
+}
+
+/// 构造TCP/UDP/ICMPv6校验和使用的伪首部，地址族不同时按IPv4映射的IPv6地址计算
+fn pseudo_header(src: IpAddr, dst: IpAddr, protocol: u8, len: usize) -> Checksum {
+    let mut checksum = Checksum::new();
+    match (src, dst) {
+        (IpAddr::V4(src), IpAddr::V4(dst)) => {
+            checksum.add(&src.octets());
+            checksum.add(&dst.octets());
+            checksum.add(&[0, protocol]);
+            checksum.add(&(len as u16).to_be_bytes());
+        }
+        (src, dst) => {
+            checksum.add(&to_ipv6(src).octets());
+            checksum.add(&to_ipv6(dst).octets());
+            checksum.add(&(len as u32).to_be_bytes());
+            checksum.add(&[0, 0, 0, protocol]);
+        }
+    }
+    checksum
+}
+
+fn to_ipv6(addr: IpAddr) -> std::net::Ipv6Addr {
+    match addr {
+        IpAddr::V4(addr) => addr.to_ipv6_mapped(),
+        IpAddr::V6(addr) => addr,
+    }
+}
+
+/// 计算带伪首部的传输层校验和，checksum_offset处的校验和字段按0计算
+fn transport_checksum(data: &[u8], checksum_offset: usize, src: IpAddr, dst: IpAddr, protocol: u8) -> u16 {
+    let mut checksum = pseudo_header(src, dst, protocol, data.len());
+    checksum.add(&data[..checksum_offset]);
+    checksum.add(&[0, 0]);
+    checksum.add(&data[checksum_offset + 2..]);
+    checksum.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Cursor, MBuf};
+
+    /// 以太网 + IPv4 + UDP，IPv4校验和已填写，UDP校验和为0
+    const FRAME: [u8; 46] = [
+        0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0x08, 0x00,
+        0x45, 0x00, 0x00, 0x20, 0x1c, 0x46, 0x40, 0x00, 0x40, 0x11, 0x00, 0x00, 0xc0, 0xa8,
+        0x00, 0x01, 0xc0, 0xa8, 0x00, 0xc7,
+        0x30, 0x39, 0x00, 0x35, 0x00, 0x0c, 0x00, 0x00, b'p', b'i', b'n', b'g',
+    ];
+
+    #[test]
+    fn test_parse_and_rewrite_stack() {
+        let mut buf = MBuf::with_capacity(64);
+        buf.append(&FRAME);
+
+        {
+            let mut cursor = Cursor::new(&buf);
+            let eth = EthernetFrame::new_checked(cursor.remaining_slice()).unwrap();
+            assert_eq!(eth.payload_ethertype(), ethertype::IPV4);
+            cursor.next_slice(eth.header_len()).unwrap();
+
+            let ip = Ipv4Packet::new_checked(cursor.remaining_slice()).unwrap();
+            assert_eq!(ip.protocol(), ip_protocol::UDP);
+            assert_eq!(ip.src().to_string(), "192.168.0.1");
+            let udp = UdpPacket::new_checked(ip.payload()).unwrap();
+            assert_eq!(udp.dst_port(), 53);
+            assert_eq!(udp.payload(), b"ping");
+        }
+
+        let mut ip = Ipv4Packet::new_checked(&mut buf[14..]).unwrap();
+        ip.fill_checksum();
+        assert!(ip.verify_checksum());
+        ip.set_ttl(ip.ttl() - 1);
+        assert!(!ip.verify_checksum());
+        ip.fill_checksum();
+        assert!(ip.verify_checksum());
+        let (src, dst) = (ip.src().into(), ip.dst().into());
+
+        let mut udp = UdpPacket::new_checked(ip.payload_mut()).unwrap();
+        udp.set_dst_port(5353);
+        udp.fill_checksum(src, dst);
+        assert!(udp.verify_checksum(src, dst));
+        assert_eq!(&buf[36..38], &5353u16.to_be_bytes());
+    }
+
+    #[test]
+    fn test_truncated() {
+        assert_eq!(EthernetFrame::new_checked(&FRAME[..10]).err(), Some(Error::Truncated));
+        assert_eq!(Ipv4Packet::new_checked(&FRAME[14..30]).err(), Some(Error::Truncated));
+        assert_eq!(UdpPacket::new_checked(&FRAME[34..40]).err(), Some(Error::Truncated));
+    }
+}
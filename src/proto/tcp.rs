@@ -0,0 +1,286 @@
+// MIT License
+//
+// Copyright (c) 2023 gaosg
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::net::IpAddr;
+use super::{get_u16, get_u32, ip_protocol, set_u16, set_u32, transport_checksum, Error, Result};
+
+/// TCP报文视图
+pub struct TcpPacket<T> {
+    buffer: T,
+}
+
+impl<T: AsRef<[u8]>> TcpPacket<T> {
+    /// 不带选项的头部长度
+    pub const MIN_HEADER_LEN: usize = 20;
+    pub const FIN: u8 = 0x01;
+    pub const SYN: u8 = 0x02;
+    pub const RST: u8 = 0x04;
+    pub const PSH: u8 = 0x08;
+    pub const ACK: u8 = 0x10;
+    pub const URG: u8 = 0x20;
+    pub const ECE: u8 = 0x40;
+    pub const CWR: u8 = 0x80;
+
+    /// 校验数据偏移并创建视图
+    pub fn new_checked(buffer: T) -> Result<Self> {
+        if buffer.as_ref().len() < Self::MIN_HEADER_LEN {
+            return Err(Error::Truncated);
+        }
+        let packet = Self { buffer };
+        if packet.header_len() < Self::MIN_HEADER_LEN {
+            return Err(Error::Malformed);
+        }
+        if packet.buffer.as_ref().len() < packet.header_len() {
+            return Err(Error::Truncated);
+        }
+        Ok(packet)
+    }
+
+    /// 取回底层缓冲区
+    pub fn into_inner(self) -> T {
+        self.buffer
+    }
+
+    /// 源端口
+    pub fn src_port(&self) -> u16 {
+        get_u16(self.buffer.as_ref(), 0)
+    }
+
+    /// 目的端口
+    pub fn dst_port(&self) -> u16 {
+        get_u16(self.buffer.as_ref(), 2)
+    }
+
+    /// 序号
+    pub fn seq(&self) -> u32 {
+        get_u32(self.buffer.as_ref(), 4)
+    }
+
+    /// 确认号
+    pub fn ack(&self) -> u32 {
+        get_u32(self.buffer.as_ref(), 8)
+    }
+
+    /// 包括选项在内的头部长度（字节）
+    pub fn header_len(&self) -> usize {
+        (self.buffer.as_ref()[12] >> 4) as usize * 4
+    }
+
+    /// 标志位，见FIN/SYN等常量
+    pub fn flags(&self) -> u8 {
+        self.buffer.as_ref()[13]
+    }
+
+    /// 窗口大小
+    pub fn window(&self) -> u16 {
+        get_u16(self.buffer.as_ref(), 14)
+    }
+
+    /// 校验和
+    pub fn checksum(&self) -> u16 {
+        get_u16(self.buffer.as_ref(), 16)
+    }
+
+    /// 紧急指针
+    pub fn urgent(&self) -> u16 {
+        get_u16(self.buffer.as_ref(), 18)
+    }
+
+    /// 选项的原始数据
+    pub fn options(&self) -> &[u8] {
+        &self.buffer.as_ref()[Self::MIN_HEADER_LEN..self.header_len()]
+    }
+
+    /// 遍历选项
+    pub fn option_iter(&self) -> TcpOptions<'_> {
+        TcpOptions { data: self.options() }
+    }
+
+    /// 负载数据
+    pub fn payload(&self) -> &[u8] {
+        &self.buffer.as_ref()[self.header_len()..]
+    }
+
+    /// 按伪首部校验
+    pub fn verify_checksum(&self, src: IpAddr, dst: IpAddr) -> bool {
+        transport_checksum(self.buffer.as_ref(), 16, src, dst, ip_protocol::TCP) == self.checksum()
+    }
+}
+
+impl<T: AsRef<[u8]> + AsMut<[u8]>> TcpPacket<T> {
+    /// 设置源端口
+    pub fn set_src_port(&mut self, value: u16) {
+        set_u16(self.buffer.as_mut(), 0, value);
+    }
+
+    /// 设置目的端口
+    pub fn set_dst_port(&mut self, value: u16) {
+        set_u16(self.buffer.as_mut(), 2, value);
+    }
+
+    /// 设置序号
+    pub fn set_seq(&mut self, value: u32) {
+        set_u32(self.buffer.as_mut(), 4, value);
+    }
+
+    /// 设置确认号
+    pub fn set_ack(&mut self, value: u32) {
+        set_u32(self.buffer.as_mut(), 8, value);
+    }
+
+    /// 设置标志位
+    pub fn set_flags(&mut self, value: u8) {
+        self.buffer.as_mut()[13] = value;
+    }
+
+    /// 设置窗口大小
+    pub fn set_window(&mut self, value: u16) {
+        set_u16(self.buffer.as_mut(), 14, value);
+    }
+
+    /// 设置紧急指针
+    pub fn set_urgent(&mut self, value: u16) {
+        set_u16(self.buffer.as_mut(), 18, value);
+    }
+
+    /// 按伪首部重新计算并填写校验和
+    pub fn fill_checksum(&mut self, src: IpAddr, dst: IpAddr) {
+        let checksum = transport_checksum(self.buffer.as_ref(), 16, src, dst, ip_protocol::TCP);
+        set_u16(self.buffer.as_mut(), 16, checksum);
+    }
+
+    /// 可变的选项数据
+    pub fn options_mut(&mut self) -> &mut [u8] {
+        let header_len = self.header_len();
+        &mut self.buffer.as_mut()[Self::MIN_HEADER_LEN..header_len]
+    }
+
+    /// 可变的负载数据
+    pub fn payload_mut(&mut self) -> &mut [u8] {
+        let header_len = self.header_len();
+        &mut self.buffer.as_mut()[header_len..]
+    }
+}
+
+/// TCP选项
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TcpOption<'a> {
+    /// 选项列表结束
+    End,
+    /// 填充
+    Nop,
+    /// 最大报文段长度
+    Mss(u16),
+    /// 窗口扩大因子
+    WindowScale(u8),
+    /// 允许SACK
+    SackPermitted,
+    /// SACK块，每8字节为一个(左边界, 右边界)
+    Sack(&'a [u8]),
+    /// 时间戳(TSval, TSecr)
+    Timestamps(u32, u32),
+    /// 其他选项，data不含类型和长度字节
+    Other { kind: u8, data: &'a [u8] },
+}
+
+/// TCP选项迭代器，遇到格式错误的选项时返回Err并停止
+pub struct TcpOptions<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Iterator for TcpOptions<'a> {
+    type Item = Result<TcpOption<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (&kind, rest) = self.data.split_first()?;
+        match kind {
+            0 => {
+                self.data = &[];
+                return Some(Ok(TcpOption::End));
+            }
+            1 => {
+                self.data = rest;
+                return Some(Ok(TcpOption::Nop));
+            }
+            _ => {}
+        }
+
+        let len = rest.first().map_or(0, |&len| len as usize);
+        if len < 2 || len > self.data.len() {
+            self.data = &[];
+            return Some(Err(Error::Malformed));
+        }
+        let data = &self.data[2..len];
+        self.data = &self.data[len..];
+        let option = match (kind, data.len()) {
+            (2, 2) => TcpOption::Mss(get_u16(data, 0)),
+            (3, 1) => TcpOption::WindowScale(data[0]),
+            (4, 0) => TcpOption::SackPermitted,
+            (5, n) if n % 8 == 0 => TcpOption::Sack(data),
+            (8, 8) => TcpOption::Timestamps(get_u32(data, 0), get_u32(data, 4)),
+            (2..=5 | 8, _) => return Some(Err(Error::Malformed)),
+            _ => TcpOption::Other { kind, data },
+        };
+        Some(Ok(option))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_syn_options_and_checksum() {
+        let mut segment = vec![
+            0x30, 0x39, 0x00, 0x50, 0, 0, 0, 1, 0, 0, 0, 0, 0x80, TcpPacket::<&[u8]>::SYN, 0xff, 0xff,
+            0, 0, 0, 0,
+        ];
+        segment.extend_from_slice(&[2, 4, 0x05, 0xb4, 1, 3, 3, 7, 4, 2, 8, 10, 0, 0, 0, 9, 0, 0, 0, 0]);
+        segment.extend_from_slice(&[0, 0, 0, 0]);
+        segment[12] = ((segment.len() as u8) / 4) << 4;
+
+        let mut tcp = TcpPacket::new_checked(&mut segment[..]).unwrap();
+        let options: Vec<_> = tcp.option_iter().map(|option| option.unwrap()).collect();
+        assert_eq!(
+            options,
+            vec![
+                TcpOption::Mss(1460),
+                TcpOption::Nop,
+                TcpOption::WindowScale(7),
+                TcpOption::SackPermitted,
+                TcpOption::Timestamps(9, 0),
+                TcpOption::End,
+            ]
+        );
+
+        let (src, dst) = ("10.0.0.1".parse().unwrap(), "10.0.0.2".parse().unwrap());
+        tcp.set_flags(TcpPacket::<&[u8]>::SYN | TcpPacket::<&[u8]>::ACK);
+        tcp.set_ack(100);
+        tcp.fill_checksum(src, dst);
+        assert!(tcp.verify_checksum(src, dst));
+        assert_eq!(tcp.ack(), 100);
+        assert!(tcp.payload().is_empty());
+
+        segment[12] = 0x40;
+        assert_eq!(TcpPacket::new_checked(&segment[..]).err(), Some(Error::Malformed));
+    }
+}
@@ -0,0 +1,138 @@
+// MIT License
+//
+// Copyright (c) 2023 gaosg
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::net::IpAddr;
+use super::{get_u16, ip_protocol, set_u16, transport_checksum, Error, Result};
+
+/// UDP报文视图
+pub struct UdpPacket<T> {
+    buffer: T,
+}
+
+impl<T: AsRef<[u8]>> UdpPacket<T> {
+    /// 头部长度
+    pub const HEADER_LEN: usize = 8;
+
+    /// 校验长度字段并创建视图
+    pub fn new_checked(buffer: T) -> Result<Self> {
+        let data = buffer.as_ref();
+        if data.len() < Self::HEADER_LEN {
+            return Err(Error::Truncated);
+        }
+        let packet = Self { buffer };
+        let len = packet.len() as usize;
+        if len < Self::HEADER_LEN {
+            return Err(Error::Malformed);
+        }
+        if packet.buffer.as_ref().len() < len {
+            return Err(Error::Truncated);
+        }
+        Ok(packet)
+    }
+
+    /// 取回底层缓冲区
+    pub fn into_inner(self) -> T {
+        self.buffer
+    }
+
+    /// 源端口
+    pub fn src_port(&self) -> u16 {
+        get_u16(self.buffer.as_ref(), 0)
+    }
+
+    /// 目的端口
+    pub fn dst_port(&self) -> u16 {
+        get_u16(self.buffer.as_ref(), 2)
+    }
+
+    /// 包括头部在内的长度
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> u16 {
+        get_u16(self.buffer.as_ref(), 4)
+    }
+
+    /// 校验和
+    pub fn checksum(&self) -> u16 {
+        get_u16(self.buffer.as_ref(), 6)
+    }
+
+    /// 负载数据
+    pub fn payload(&self) -> &[u8] {
+        let data = self.buffer.as_ref();
+        &data[Self::HEADER_LEN..self.end()]
+    }
+
+    /// 按伪首部校验；IPv4下校验和为0表示未计算，视为正确
+    pub fn verify_checksum(&self, src: IpAddr, dst: IpAddr) -> bool {
+        if src.is_ipv4() && self.checksum() == 0 {
+            return true;
+        }
+        let expected = self.compute_checksum(src, dst);
+        expected == self.checksum()
+    }
+
+    fn compute_checksum(&self, src: IpAddr, dst: IpAddr) -> u16 {
+        match transport_checksum(&self.buffer.as_ref()[..self.end()], 6, src, dst, ip_protocol::UDP) {
+            // 计算结果为0时按RFC 768发送全1
+            0 => 0xffff,
+            checksum => checksum,
+        }
+    }
+
+    fn end(&self) -> usize {
+        (self.len() as usize).clamp(Self::HEADER_LEN, self.buffer.as_ref().len())
+    }
+}
+
+impl<T: AsRef<[u8]> + AsMut<[u8]>> UdpPacket<T> {
+    /// 设置源端口
+    pub fn set_src_port(&mut self, value: u16) {
+        set_u16(self.buffer.as_mut(), 0, value);
+    }
+
+    /// 设置目的端口
+    pub fn set_dst_port(&mut self, value: u16) {
+        set_u16(self.buffer.as_mut(), 2, value);
+    }
+
+    /// 设置长度
+    pub fn set_len(&mut self, value: u16) {
+        set_u16(self.buffer.as_mut(), 4, value);
+    }
+
+    /// 设置校验和
+    pub fn set_checksum(&mut self, value: u16) {
+        set_u16(self.buffer.as_mut(), 6, value);
+    }
+
+    /// 按伪首部重新计算并填写校验和
+    pub fn fill_checksum(&mut self, src: IpAddr, dst: IpAddr) {
+        let checksum = self.compute_checksum(src, dst);
+        self.set_checksum(checksum);
+    }
+
+    /// 可变的负载数据
+    pub fn payload_mut(&mut self) -> &mut [u8] {
+        let end = self.end();
+        &mut self.buffer.as_mut()[Self::HEADER_LEN..end]
+    }
+}
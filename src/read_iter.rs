@@ -0,0 +1,98 @@
+// MIT License
+//
+// Copyright (c) 2023 gaosg
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::io::{self, Read};
+use std::sync::Arc;
+use super::{MBuf, MPool};
+use super::mbuf_pool::AllocSite;
+
+/// 从Read中读取数据的迭代器，每次从池中分配一个MBuf并读入一次，读到文件结尾时结束
+pub struct PooledReadIter<R> {
+    reader: R,
+    pool: Arc<MPool>,
+    site: AllocSite,
+    done: bool,
+}
+
+impl<R: Read> PooledReadIter<R> {
+    /// 从reader读取，缓冲区从pool中分配
+    ///
+    /// 启用`tracking`特性时，迭代出的缓冲区记在调用`new`的位置，不受`for`循环或迭代器适配器影响
+    #[cfg_attr(feature = "tracking", track_caller)]
+    pub fn new(reader: R, pool: Arc<MPool>) -> Self {
+        Self { reader, pool, site: AllocSite::caller(), done: false }
+    }
+
+    /// 取回底层读取器
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: Read> Iterator for PooledReadIter<R> {
+    type Item = io::Result<MBuf>;
+
+    fn next(&mut self) -> Option<io::Result<MBuf>> {
+        if self.done {
+            return None;
+        }
+        let mut buf = self.pool.alloc_at(self.site);
+        if buf.tailroom() == 0 {
+            buf.reserve(self.pool.capacity().max(64));
+        }
+        loop {
+            match self.reader.read(buf.spare_capacity_mut()) {
+                Ok(0) => {
+                    self.done = true;
+                    self.pool.free(buf);
+                    return None;
+                }
+                Ok(n) => {
+                    buf.commit(n);
+                    return Some(Ok(buf));
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => {
+                    self.pool.free(buf);
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pooled_read_iter() {
+        let pool = Arc::new(MPool::new(4, 4));
+        let data = b"0123456789";
+        let bufs: Vec<MBuf> = PooledReadIter::new(&data[..], pool.clone())
+            .map(|buf| buf.unwrap())
+            .collect();
+        assert_eq!(bufs.iter().map(|buf| buf.len()).collect::<Vec<_>>(), [4, 4, 2]);
+        assert_eq!(bufs.concat(), data);
+        assert_eq!(pool.allocated_count(), 3);
+    }
+}
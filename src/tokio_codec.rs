@@ -0,0 +1,138 @@
+// MIT License
+//
+// Copyright (c) 2023 gaosg
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::io;
+use std::sync::Arc;
+use bytes::{Buf, BufMut, BytesMut};
+use tokio_util::codec;
+use super::framing::{Decoder, Encoder};
+use super::{MBuf, MPool};
+use super::mbuf_pool::AllocSite;
+
+/// 将framing中的编解码器适配为`tokio_util::codec`的`Decoder`/`Encoder`
+///
+/// 帧先由`tokio_util`读入`BytesMut`，解出时再复制一次到从池中分配的`MBuf`；
+/// 需要从读取到分帧都使用池中缓冲区时使用`tokio_io::PooledFramedRead`。编码后的`MBuf`随即释放，池分配的回到各自的池中
+pub struct PooledCodec<C> {
+    inner: C,
+    pool: Arc<MPool>,
+    site: AllocSite,
+}
+
+impl<C> PooledCodec<C> {
+    /// 使用inner分帧，帧从pool中分配
+    ///
+    /// 启用`tracking`特性时，解出的帧记在调用`new`的位置，而不是`tokio_util`内部
+    #[cfg_attr(feature = "tracking", track_caller)]
+    pub fn new(inner: C, pool: Arc<MPool>) -> Self {
+        Self { inner, pool, site: AllocSite::caller() }
+    }
+
+    /// 获取使用的内存池
+    pub fn pool(&self) -> &Arc<MPool> {
+        &self.pool
+    }
+
+    /// 获取内部的编解码器
+    pub fn get_ref(&self) -> &C {
+        &self.inner
+    }
+
+    /// 取回内部的编解码器
+    pub fn into_inner(self) -> C {
+        self.inner
+    }
+}
+
+impl<C: Decoder> codec::Decoder for PooledCodec<C> {
+    type Item = MBuf;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> io::Result<Option<MBuf>> {
+        let Some(frame) = self.inner.find_frame(src)? else {
+            return Ok(None);
+        };
+        let mut buf = self.pool.alloc_at(self.site);
+        buf.append(&src[frame.payload]);
+        src.advance(frame.consumed);
+        Ok(Some(buf))
+    }
+}
+
+impl<C: Encoder> codec::Encoder<MBuf> for PooledCodec<C> {
+    type Error = io::Error;
+
+    fn encode(&mut self, item: MBuf, dst: &mut BytesMut) -> io::Result<()> {
+        // item在这里释放，池分配的回到各自的池中
+        codec::Encoder::encode(self, &item[..], dst)
+    }
+}
+
+impl<C: Encoder> codec::Encoder<&[u8]> for PooledCodec<C> {
+    type Error = io::Error;
+
+    fn encode(&mut self, item: &[u8], dst: &mut BytesMut) -> io::Result<()> {
+        self.inner.encode(item, &mut dst.writer())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::framing::{LengthDelimited, Lines};
+    use futures_util::{SinkExt, StreamExt};
+    use tokio_util::codec::{Decoder as _, FramedRead, FramedWrite};
+
+    #[test]
+    fn test_decode_into_pool() {
+        let pool = Arc::new(MPool::new(2, 64));
+        let mut codec = PooledCodec::new(Lines::new(), pool.clone());
+        let mut src = BytesMut::from(&b"one\r\ntwo\nthr"[..]);
+        let one = codec.decode(&mut src).unwrap().unwrap();
+        let two = codec.decode(&mut src).unwrap().unwrap();
+        assert!(codec.decode(&mut src).unwrap().is_none());
+        assert_eq!((&one[..], &two[..], &src[..]), (&b"one"[..], &b"two"[..], &b"thr"[..]));
+        assert_eq!(one.capacity(), 64);
+        assert_eq!(pool.allocated_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_framed_roundtrip() {
+        let pool = Arc::new(MPool::new(4, 64));
+        let codec = PooledCodec::new(LengthDelimited::new(2), pool.clone());
+        let mut writer = FramedWrite::new(Vec::new(), codec);
+        let mut buf = pool.alloc();
+        buf.append(b"pooled");
+        writer.send(buf).await.unwrap();
+        writer.send(&b"slice"[..]).await.unwrap();
+        let wire = writer.into_inner();
+        assert_eq!(&wire[..], b"\x00\x06pooled\x00\x05slice");
+
+        let codec = PooledCodec::new(LengthDelimited::new(2), pool.clone());
+        let frames: Vec<MBuf> = FramedRead::new(&wire[..], codec)
+            .map(|frame| frame.unwrap())
+            .collect()
+            .await;
+        assert_eq!(frames.len(), 2);
+        assert_eq!((&frames[0][..], &frames[1][..]), (&b"pooled"[..], &b"slice"[..]));
+    }
+}
@@ -0,0 +1,473 @@
+// MIT License
+//
+// Copyright (c) 2023 gaosg
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! tokio的`AsyncRead`/`AsyncWrite`实现及异步读写辅助函数
+//!
+//! 缓冲区都在内存中，读写总是立即完成
+
+use std::io::{self, IoSlice, Read, Write};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{ready, Context, Poll};
+use futures_core::Stream;
+use futures_sink::Sink;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use crate::async_util::{impl_async_write, read_into_with, write_chain_with};
+use crate::framing::Decoder;
+use crate::mbuf_pool::AllocSite;
+use crate::mchain::IOV_BATCH;
+use super::{ChainCursor, Cursor, MBuf, MChain, MPool};
+
+/// 用同步Read实现的poll_read
+fn poll_read_from<R: Read>(reader: &mut R, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+    let n = reader.read(buf.initialize_unfilled())?;
+    buf.advance(n);
+    Poll::Ready(Ok(()))
+}
+
+impl AsyncRead for Cursor<'_> {
+    fn poll_read(self: Pin<&mut Self>, _cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        poll_read_from(self.get_mut(), buf)
+    }
+}
+
+impl AsyncRead for ChainCursor<'_> {
+    fn poll_read(self: Pin<&mut Self>, _cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        poll_read_from(self.get_mut(), buf)
+    }
+}
+
+/// 读取后丢弃已读的数据
+impl AsyncRead for MBuf {
+    fn poll_read(self: Pin<&mut Self>, _cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        poll_read_from(self.get_mut(), buf)
+    }
+}
+
+impl_async_write!(AsyncWrite, poll_shutdown, [MBuf, MChain], {
+    fn is_write_vectored(&self) -> bool {
+        true
+    }
+});
+
+/// 从reader读取数据直接写入buf的空闲空间，没有空闲空间时先扩容，返回读取的字节数
+pub async fn read_into<R: AsyncRead + Unpin + ?Sized>(buf: &mut MBuf, reader: &mut R) -> io::Result<usize> {
+    read_into_with(buf, |cx, spare| {
+        let mut spare = ReadBuf::new(spare);
+        ready!(Pin::new(&mut *reader).poll_read(cx, &mut spare))?;
+        Poll::Ready(Ok(spare.filled().len()))
+    })
+    .await
+}
+
+/// 用向量写将链中的数据全部写入writer，已写出的数据从链中移除，返回写出的字节数
+pub async fn write_chain<W: AsyncWrite + Unpin + ?Sized>(chain: &mut MChain, writer: &mut W) -> io::Result<usize> {
+    write_chain_with(chain, |cx, iov| Pin::new(&mut *writer).poll_write_vectored(cx, iov)).await
+}
+
+/// 从AsyncRead中读取数据的流，每次从池中分配一个MBuf并读入一次，读到文件结尾时结束
+pub struct PooledReadStream<R> {
+    reader: R,
+    pool: Arc<MPool>,
+    site: AllocSite,
+    /// 上次读取未就绪时保留的缓冲区
+    buf: Option<MBuf>,
+    done: bool,
+}
+
+impl<R: AsyncRead + Unpin> PooledReadStream<R> {
+    /// 从reader读取，缓冲区从pool中分配
+    ///
+    /// 启用`tracking`特性时，流分配的缓冲区都记在调用`new`的位置
+    #[cfg_attr(feature = "tracking", track_caller)]
+    pub fn new(reader: R, pool: Arc<MPool>) -> Self {
+        Self { reader, pool, site: AllocSite::caller(), buf: None, done: false }
+    }
+
+    /// 取回底层读取器
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: AsyncRead + Unpin> Stream for PooledReadStream<R> {
+    type Item = io::Result<MBuf>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<io::Result<MBuf>>> {
+        let this = self.get_mut();
+        if this.done {
+            return Poll::Ready(None);
+        }
+        let (pool, site) = (&this.pool, this.site);
+        let buf = this.buf.get_or_insert_with(|| {
+            let mut buf = pool.alloc_at(site);
+            if buf.tailroom() == 0 {
+                buf.reserve(pool.capacity().max(64));
+            }
+            buf
+        });
+
+        let mut spare = ReadBuf::new(buf.spare_capacity_mut());
+        let result = ready!(Pin::new(&mut this.reader).poll_read(cx, &mut spare));
+        let n = spare.filled().len();
+        let mut buf = this.buf.take().unwrap();
+        match result {
+            Ok(()) if n > 0 => {
+                buf.commit(n);
+                Poll::Ready(Some(Ok(buf)))
+            }
+            Ok(()) => {
+                this.done = true;
+                this.pool.free(buf);
+                Poll::Ready(None)
+            }
+            Err(e) => {
+                this.pool.free(buf);
+                Poll::Ready(Some(Err(e)))
+            }
+        }
+    }
+}
+
+/// 从AsyncRead直接读入池中的MBuf并分帧的流，帧与读入的缓冲区共享内存，不经过复制
+///
+/// 缓冲区写满时把未完成的帧移到新分配的池缓冲区；单帧超过池容量时扩容
+pub struct PooledFramedRead<R, D> {
+    reader: R,
+    decoder: D,
+    pool: Arc<MPool>,
+    site: AllocSite,
+    buf: Option<MBuf>,
+    eof: bool,
+}
+
+impl<R: AsyncRead + Unpin, D: Decoder> PooledFramedRead<R, D> {
+    /// 从reader读取并用decoder分帧，缓冲区从pool中分配
+    ///
+    /// 启用`tracking`特性时，读入用的缓冲区记在调用`new`的位置
+    #[cfg_attr(feature = "tracking", track_caller)]
+    pub fn new(reader: R, decoder: D, pool: Arc<MPool>) -> Self {
+        Self { reader, decoder, pool, site: AllocSite::caller(), buf: None, eof: false }
+    }
+
+    /// 获取内部的解码器
+    pub fn decoder(&self) -> &D {
+        &self.decoder
+    }
+
+    /// 取回底层读取器，已读入但未取出的数据被丢弃
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// 为下一次读取准备空闲空间
+    fn make_room(&mut self) {
+        let buf = self.buf.get_or_insert_with(|| self.pool.alloc_at(self.site));
+        if buf.tailroom() > 0 {
+            return;
+        }
+        if !buf.is_unique() && buf.len() < self.pool.capacity() {
+            // 前面的帧仍在使用这块内存，未完成的部分移到新的池缓冲区
+            let mut fresh = self.pool.alloc_at(self.site);
+            fresh.append(buf);
+            *buf = fresh;
+        } else if buf.len() < buf.capacity() {
+            // 独占时把数据移到开头
+            buf.reserve(buf.capacity() - buf.len());
+        } else {
+            buf.reserve(buf.capacity().max(64));
+        }
+    }
+}
+
+impl<R: AsyncRead + Unpin, D: Decoder + Unpin> Stream for PooledFramedRead<R, D> {
+    type Item = io::Result<MBuf>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<io::Result<MBuf>>> {
+        let this = self.get_mut();
+        loop {
+            if let Some(buf) = this.buf.as_mut() {
+                match this.decoder.decode(buf) {
+                    Ok(Some(frame)) => return Poll::Ready(Some(Ok(frame))),
+                    Ok(None) => {}
+                    Err(e) => return Poll::Ready(Some(Err(e))),
+                }
+            }
+            if this.eof {
+                return match this.buf.take() {
+                    Some(buf) if !buf.is_empty() => {
+                        Poll::Ready(Some(Err(io::Error::new(io::ErrorKind::UnexpectedEof, "bytes remaining on stream"))))
+                    }
+                    _ => Poll::Ready(None),
+                };
+            }
+
+            this.make_room();
+            let buf = this.buf.as_mut().unwrap();
+            let mut spare = ReadBuf::new(buf.spare_capacity_mut());
+            ready!(Pin::new(&mut this.reader).poll_read(cx, &mut spare))?;
+            let n = spare.filled().len();
+            if n == 0 {
+                this.eof = true;
+            }
+            buf.commit(n);
+        }
+    }
+}
+
+/// 将MBuf或MChain写入AsyncWrite的Sink，使用向量写，完全写出的缓冲区随即释放，池分配的回到各自的池中
+pub struct MBufSink<W> {
+    writer: W,
+    pending: MChain,
+    max_pending: usize,
+}
+
+impl<W: AsyncWrite + Unpin> MBufSink<W> {
+    /// 写入writer
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            pending: MChain::new(),
+            max_pending: 64 * 1024,
+        }
+    }
+
+    /// 设置待写出数据的上限，超过时`poll_ready`先写出已有数据
+    pub fn with_max_pending(mut self, max_pending: usize) -> Self {
+        self.max_pending = max_pending;
+        self
+    }
+
+    /// 获取尚未写出的字节数
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// 取回底层写入器，未写出的数据被丢弃
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// 写出全部待写数据
+    fn poll_write_pending(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while !self.pending.is_empty() {
+            let mut iov = [IoSlice::new(&[]); IOV_BATCH];
+            let count = self.pending.as_io_slices(&mut iov);
+            let mut n = ready!(Pin::new(&mut self.writer).poll_write_vectored(cx, &iov[..count]))?;
+            if n == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            while let Some(front) = self.pending.front() {
+                if front.len() > n {
+                    self.pending.advance(n);
+                    break;
+                }
+                n -= front.len();
+                self.pending.pop_front();
+            }
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<W: AsyncWrite + Unpin> Sink<MChain> for MBufSink<W> {
+    type Error = io::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if this.pending.len() >= this.max_pending {
+            ready!(this.poll_write_pending(cx))?;
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(self: Pin<&mut Self>, mut item: MChain) -> io::Result<()> {
+        self.get_mut().pending.append(&mut item);
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_write_pending(cx))?;
+        Pin::new(&mut this.writer).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_write_pending(cx))?;
+        Pin::new(&mut this.writer).poll_shutdown(cx)
+    }
+}
+
+impl<W: AsyncWrite + Unpin> Sink<MBuf> for MBufSink<W> {
+    type Error = io::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Sink::<MChain>::poll_ready(self, cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: MBuf) -> io::Result<()> {
+        self.get_mut().pending.push_back(item);
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Sink::<MChain>::poll_flush(self, cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Sink::<MChain>::poll_close(self, cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::IoSlice;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use std::sync::Arc;
+    use futures_util::{SinkExt, StreamExt};
+    use super::{read_into, write_chain, MBufSink, PooledFramedRead, PooledReadStream};
+    use crate::framing::{Encoder, LengthDelimited};
+    use crate::mchain::chain_of;
+    use crate::{Cursor, MBuf, MChain, MPool};
+
+    #[tokio::test]
+    async fn test_async_read() {
+        let mut buf = MBuf::with_capacity(16);
+        buf.append(b"async data");
+
+        let mut out = Vec::new();
+        Cursor::new(&buf).read_to_end(&mut out).await.unwrap();
+        assert_eq!(out, b"async data");
+
+        let chain: MChain = [buf.slice(..5), buf.slice(5..)].into_iter().collect();
+        let mut out = [0u8; 10];
+        chain.cursor().read_exact(&mut out).await.unwrap();
+        assert_eq!(&out, b"async data");
+
+        let mut head = [0u8; 6];
+        buf.read_exact(&mut head).await.unwrap();
+        assert_eq!(&buf[..], b"data");
+    }
+
+    #[tokio::test]
+    async fn test_async_write() {
+        let mut buf = MBuf::with_capacity(4);
+        buf.write_all(b"hello ").await.unwrap();
+        assert_eq!(buf.write_vectored(&[IoSlice::new(b"wor"), IoSlice::new(b"ld")]).await.unwrap(), 5);
+        assert_eq!(&buf[..], b"hello world");
+
+        let mut chain = MChain::new();
+        chain.write_all(b"chained").await.unwrap();
+        assert_eq!(chain.to_vec(), b"chained");
+    }
+
+    #[tokio::test]
+    async fn test_read_into_write_chain() {
+        let (mut client, mut server) = tokio::io::duplex(8);
+        let mut chain = chain_of(&[b"abc", b"defgh", b"ijklmn"]);
+
+        let writer = async {
+            let n = write_chain(&mut chain, &mut client).await.unwrap();
+            drop(client);
+            n
+        };
+        let reader = async {
+            let mut buf = MBuf::with_capacity(0);
+            while read_into(&mut buf, &mut server).await.unwrap() > 0 {}
+            buf
+        };
+        let (n, buf) = tokio::join!(writer, reader);
+        assert_eq!(n, 14);
+        assert!(chain.is_empty());
+        assert_eq!(&buf[..], b"abcdefghijklmn");
+    }
+
+    #[tokio::test]
+    async fn test_pooled_read_stream() {
+        let pool = Arc::new(MPool::new(4, 4));
+        let data = b"0123456789";
+        let bufs: Vec<MBuf> = PooledReadStream::new(&data[..], pool.clone())
+            .map(|buf| buf.unwrap())
+            .collect()
+            .await;
+        assert_eq!(bufs.iter().map(|buf| buf.len()).collect::<Vec<_>>(), [4, 4, 2]);
+        assert_eq!(bufs.concat(), data);
+        assert_eq!(pool.allocated_count(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_pooled_framed_read() {
+        let mut codec = LengthDelimited::new(1);
+        let mut wire = Vec::new();
+        let long = [0x42; 40];
+        for payload in [&b"first"[..], b"second", &long, b"", b"last"] {
+            codec.encode(payload, &mut wire).unwrap();
+        }
+
+        let pool = Arc::new(MPool::new(0, 16));
+        let frames: Vec<MBuf> = PooledFramedRead::new(&wire[..], codec, pool.clone())
+            .map(|frame| frame.unwrap())
+            .collect()
+            .await;
+        let payloads: Vec<&[u8]> = frames.iter().map(|frame| &frame[..]).collect();
+        assert_eq!(payloads, [&b"first"[..], b"second", &long, b"", b"last"]);
+        // 帧释放后内存回到池中
+        drop(frames);
+        assert_eq!(pool.allocated_count(), 0);
+
+        // 流在帧中间结束
+        let mut frames = PooledFramedRead::new(&wire[..3], LengthDelimited::new(1), pool.clone());
+        assert!(frames.next().await.unwrap().is_err());
+        assert!(frames.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_mbuf_sink() {
+        let pool = Arc::new(MPool::new(0, 8));
+        let (client, mut server) = tokio::io::duplex(4);
+        let mut sink = MBufSink::new(client).with_max_pending(4);
+
+        let writer = async {
+            let mut head = pool.alloc();
+            head.append(b"head|");
+            sink.send(head).await.unwrap();
+            let mut chain = MChain::new();
+            for part in [&b"seg1|"[..], b"seg2"] {
+                let mut seg = pool.alloc();
+                seg.append(part);
+                chain.push_back(seg);
+            }
+            sink.send(chain).await.unwrap();
+            assert_eq!(sink.pending(), 0);
+            SinkExt::<MBuf>::close(&mut sink).await.unwrap();
+        };
+        let reader = async {
+            let mut out = Vec::new();
+            server.read_to_end(&mut out).await.unwrap();
+            out
+        };
+        let ((), out) = tokio::join!(writer, reader);
+        assert_eq!(out, b"head|seg1|seg2");
+        assert_eq!(pool.allocated_count(), 0);
+    }
+}
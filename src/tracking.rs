@@ -0,0 +1,200 @@
+// MIT License
+//
+// Copyright (c) 2023 gaosg
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! 记录MPool中尚未归还的MBuf的分配位置，用于定位忘记调用`free`的代码
+
+use std::collections::HashMap;
+use std::fmt;
+use std::panic::Location;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// 池内的分配记录，以分配时发放的编号为键
+#[derive(Default)]
+pub(crate) struct Tracker {
+    next_id: AtomicU64,
+    live: Mutex<HashMap<u64, (&'static Location<'static>, Instant)>>,
+}
+
+impl Tracker {
+    /// 记录一次分配，返回写入MBuf的编号
+    pub(crate) fn record(&self, location: &'static Location<'static>) -> u64 {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.live.lock().unwrap().insert(id, (location, Instant::now()));
+        id
+    }
+
+    /// 删除已归还的分配记录
+    pub(crate) fn forget(&self, id: u64) {
+        self.live.lock().unwrap().remove(&id);
+    }
+
+    /// 按分配位置汇总当前未归还的记录
+    pub(crate) fn report(&self) -> OutstandingReport {
+        let now = Instant::now();
+        let mut sites: HashMap<&'static Location<'static>, Site> = HashMap::new();
+        for &(location, since) in self.live.lock().unwrap().values() {
+            let age = now.duration_since(since);
+            let site = sites.entry(location).or_insert(Site { location, count: 0, oldest: Duration::ZERO });
+            site.count += 1;
+            site.oldest = site.oldest.max(age);
+        }
+        let mut sites: Vec<Site> = sites.into_values().collect();
+        sites.sort_by(|a, b| b.oldest.cmp(&a.oldest).then(b.count.cmp(&a.count)));
+        OutstandingReport { sites }
+    }
+}
+
+/// 同一位置分配且尚未归还的MBuf
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Site {
+    /// 调用`MPool::alloc`的位置
+    pub location: &'static Location<'static>,
+    /// 未归还的数量
+    pub count: usize,
+    /// 其中最早一个分配至今的时间
+    pub oldest: Duration,
+}
+
+/// `MPool::outstanding_report`的结果，按最长未归还时间降序排列
+#[derive(Debug, Clone, Default)]
+pub struct OutstandingReport {
+    sites: Vec<Site>,
+}
+
+impl OutstandingReport {
+    /// 各分配位置的汇总
+    pub fn sites(&self) -> &[Site] {
+        &self.sites
+    }
+
+    /// 未归还的MBuf总数
+    pub fn total(&self) -> usize {
+        self.sites.iter().map(|site| site.count).sum()
+    }
+
+    /// 是否没有未归还的MBuf
+    pub fn is_empty(&self) -> bool {
+        self.sites.is_empty()
+    }
+
+    /// 只保留最早分配至今超过age的位置，用于找出长期持有的缓冲区
+    pub fn older_than(mut self, age: Duration) -> Self {
+        self.sites.retain(|site| site.oldest >= age);
+        self
+    }
+}
+
+impl fmt::Display for OutstandingReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} outstanding buffer(s) at {} site(s)", self.total(), self.sites.len())?;
+        for site in &self.sites {
+            writeln!(f, "  {:>6}  oldest {:>10.3?}  {}", site.count, site.oldest, site.location)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use crate::MPool;
+
+    #[test]
+    fn test_outstanding_report() {
+        let pool = MPool::new(0, 16);
+        let kept: Vec<_> = (0..3).map(|_| pool.alloc()).collect();
+        let line = line!() - 1;
+        let other = pool.alloc();
+        let returned = pool.alloc();
+        pool.free(returned);
+
+        let report = pool.outstanding_report();
+        assert_eq!(report.total(), 4);
+        assert_eq!(report.sites().len(), 2);
+        let site = report.sites().iter().find(|site| site.count == 3).unwrap();
+        assert_eq!(site.location.file(), file!());
+        assert_eq!(site.location.line(), line);
+        assert!(report.to_string().starts_with("4 outstanding buffer(s) at 2 site(s)"));
+        assert!(report.clone().older_than(Duration::from_secs(3600)).is_empty());
+
+        pool.free(other);
+        for buf in kept {
+            pool.free(buf);
+        }
+        assert!(pool.outstanding_report().is_empty());
+    }
+
+    #[test]
+    fn test_caller_of_entry_point() {
+        let pool = MPool::new(0, 16);
+        let mut chain = crate::MChain::new();
+        chain.read_vectored_from(&mut &[7u8; 40][..], &pool, 4).unwrap();
+        let line = line!() - 1;
+        let report = pool.outstanding_report();
+        assert_eq!(report.sites().len(), 1);
+        assert_eq!(report.sites()[0].location.file(), file!());
+        assert_eq!(report.sites()[0].location.line(), line);
+    }
+
+    #[test]
+    fn test_iterator_site() {
+        // 经过迭代器适配器取出的缓冲区仍记在创建迭代器的位置
+        let pool = std::sync::Arc::new(MPool::new(0, 16));
+        let iter = crate::PooledReadIter::new(&[3u8; 40][..], pool.clone());
+        let line = line!() - 1;
+        let bufs: Vec<_> = iter.map(Result::unwrap).collect();
+        let report = pool.outstanding_report();
+        assert_eq!(report.total(), 3);
+        assert_eq!(report.sites().len(), 1);
+        assert_eq!(report.sites()[0].location.file(), file!());
+        assert_eq!(report.sites()[0].location.line(), line);
+        drop(bufs);
+    }
+
+    #[test]
+    #[cfg(any(feature = "deflate", feature = "gzip", feature = "lz4", feature = "zstd"))]
+    fn test_chain_writer_site() {
+        // 写入器分配的缓冲区记录为创建写入器的位置
+        let pool = MPool::new(0, 16);
+        let mut writer = crate::compress::ChainWriter::new(&pool);
+        let line = line!() - 1;
+        std::io::Write::write_all(&mut writer, &[1; 40]).unwrap();
+        let report = pool.outstanding_report();
+        assert_eq!(report.total(), 3);
+        assert_eq!(report.sites()[0].location.line(), line);
+    }
+
+    #[test]
+    fn test_resized_buffer_is_matched() {
+        let pool = MPool::new(1, 16);
+        let mut buf = pool.alloc();
+        buf.resize(1024);
+        let clone = buf.clone();
+        pool.free(buf);
+        // 克隆仍持有内存，仍算作未归还
+        assert_eq!(pool.outstanding_report().total(), 1);
+        drop(clone);
+        assert!(pool.outstanding_report().is_empty());
+    }
+}
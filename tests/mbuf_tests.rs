@@ -105,7 +105,7 @@ fn test_mbuf_thread_safety() {
     
     let handles: Vec<_> = (0..4).map(|_| {
         let buf = Arc::clone(&buf);
-        let data = data.clone();
+        let data = *data;
         thread::spawn(move || {
             let mut buf = (*buf).clone();
             buf.append(&data);